@@ -0,0 +1,394 @@
+//! This module contains the endpoints which work on many users at once
+use crate::{
+    api::user_api::{report_json, request_language},
+    current_config,
+    library::{
+        batch::{
+            import_rows, validate_rows, BatchFormat, ImportMode, ImportReport, Rejection,
+            RowReader, RowResult,
+        },
+        csv_import::{error_csv, read_csv},
+        messages::Language,
+    },
+    models::{db_models::MSG, validation_models::ValidationReport},
+    repository::mongodb_repo::MongoRepo,
+    AppState,
+};
+use actix_multipart::Multipart;
+use actix_web::{
+    http::header,
+    post,
+    web::{self, Bytes, Data, Payload, Query},
+    HttpRequest, HttpResponse,
+};
+use futures::StreamExt;
+use serde::Deserialize;
+use serde_json::Value;
+use std::sync::Arc;
+
+/// Number of rows which are validated together
+const CHUNK_ROWS: usize = 1000;
+
+/// Maximum size of a JSON array payload of `POST /users/validate:batch`, it is kept in memory until it is complete.
+/// NDJSON payloads are validated line by line and have no limit there.
+const MAX_ARRAY_BYTES: usize = 64 * 1024 * 1024;
+
+/// Maximum size of an import payload or CSV file, all rows of an import are kept in memory until they are stored
+const MAX_IMPORT_BYTES: usize = 64 * 1024 * 1024;
+
+/// Maximum number of rows of an import
+const MAX_IMPORT_ROWS: usize = 100_000;
+
+/// Picks the format of a batch payload, `Content-Type: application/x-ndjson` selects NDJSON.
+fn batch_format(req: &HttpRequest) -> BatchFormat {
+    let content_type = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    match content_type.split(';').next().map(str::trim) {
+        Some("application/x-ndjson" | "application/ndjson" | "application/jsonl") => {
+            BatchFormat::Ndjson
+        }
+        _ => BatchFormat::JsonArray,
+    }
+}
+
+/// Builds the `413 Payload Too Large` response of an import which exceeds [`MAX_IMPORT_ROWS`] or [`MAX_IMPORT_BYTES`].
+fn import_too_large() -> HttpResponse {
+    let response = MSG {
+        message: format!(
+            "Import too large, send at most {} users and {} MiB at once.",
+            MAX_IMPORT_ROWS,
+            MAX_IMPORT_BYTES / 1024 / 1024
+        ),
+    };
+    HttpResponse::PayloadTooLarge().json(response)
+}
+
+/// Lists the failed fields of a row together with its `index`.
+fn invalid_row(index: usize, report: &ValidationReport, language: Language) -> Value {
+    let mut row = report_json(report, language);
+    row["index"] = Value::from(index);
+    row
+}
+
+/// Writes the result of a row as a line `{"index": 0, "ok": true}`, invalid rows also list every failed field.
+fn row_line((index, result): RowResult, language: Language) -> Bytes {
+    let line = match result {
+        Ok(_) => serde_json::json!({ "index": index, "ok": true }),
+        Err(report) => {
+            let mut line = invalid_row(index, &report, language);
+            line["ok"] = Value::from(false);
+            line
+        }
+    };
+    Bytes::from(format!("{}\n", line))
+}
+
+/// Writes an error which ends the batch as a line `{"error": "..."}`.
+fn error_line(error: impl std::fmt::Display) -> Bytes {
+    Bytes::from(format!(
+        "{}\n",
+        serde_json::json!({ "error": error.to_string() })
+    ))
+}
+
+/// **POST /users/validate:batch** Endpoint to check many users without storing them
+///
+/// NDJSON rows are validated and answered while the payload is still arriving. A JSON array is buffered
+/// until it is complete, so its first line is only sent after the whole array (at most 64 MiB) was received.
+///
+/// # Arguments
+/// * `payload: Payload` - A JSON array of users, or one user per line with `Content-Type: application/x-ndjson`
+/// * `req: HttpRequest` - The request, its `Accept-Language` header selects the language of the error messages
+///
+/// # Returns
+/// * `HttpResponse` - A stream of NDJSON lines in the order of the rows, `{"index": 0, "ok": true}` for valid rows
+///   and `{"index": 1, "ok": false, "message": "...", "errors": [...]}` for invalid ones.
+///   A payload which can't be read ends the stream with `{"error": "..."}`
+#[post("/users/validate:batch")]
+pub async fn validate_batch(
+    state: Data<AppState>,
+    mut payload: Payload,
+    req: HttpRequest,
+) -> HttpResponse {
+    // One snapshot for the whole batch, so a reload of the config can't mix the rules of two versions
+    let config = Arc::new(current_config(&state));
+    let language = request_language(&req, &config);
+    let mut reader = RowReader::new(batch_format(&req));
+
+    let lines = async_stream::stream! {
+        let mut rows = Vec::new();
+        let mut done = false;
+        while !done {
+            match payload.next().await {
+                Some(Ok(chunk)) => {
+                    rows.extend(reader.push(&chunk));
+                    if reader.buffered() > MAX_ARRAY_BYTES {
+                        yield Ok(error_line("payload too large, send more than 64 MiB as NDJSON"));
+                        return;
+                    }
+                }
+                Some(Err(e)) => {
+                    yield Ok(error_line(e));
+                    return;
+                }
+                None => {
+                    rows.extend(reader.finish());
+                    done = true;
+                }
+            }
+
+            if rows.len() >= CHUNK_ROWS || (done && !rows.is_empty()) {
+                let config = config.clone();
+                let chunk = std::mem::take(&mut rows);
+                match web::block(move || validate_rows(&config, chunk)).await {
+                    Ok(results) => {
+                        for result in results {
+                            yield Ok::<_, actix_web::Error>(row_line(result, language));
+                        }
+                    }
+                    Err(e) => {
+                        yield Ok(error_line(e));
+                        return;
+                    }
+                }
+            }
+        }
+    };
+    HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .streaming(lines)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportQuery {
+    #[serde(default)]
+    pub mode: ImportMode,
+}
+
+/// **POST /users:import** Endpoint to create many users at once
+///
+/// # Arguments
+/// * `db: Data<MongoRepo>` - The database connection
+/// * `query: Query<ImportQuery>` - `?mode=atomic` (default) stores nothing if a row fails, `?mode=best_effort` stores the valid rows
+/// * `payload: Payload` - A JSON array of users, or one user per line with `Content-Type: application/x-ndjson`
+/// * `req: HttpRequest` - The request, its `Accept-Language` header selects the language of the error messages
+///
+/// # Returns
+/// * `HttpResponse` - The `inserted` rows with their `index` and `id` and the `rejected` rows with their `index` and every failed field.
+///   An atomic import with invalid rows answers with `400 Bad Request` and stores nothing,
+///   more than 100 000 rows or 64 MiB are refused with `413 Payload Too Large`
+#[post("/users:import")]
+pub async fn import_users(
+    state: Data<AppState>,
+    db: Data<MongoRepo>,
+    query: Query<ImportQuery>,
+    mut payload: Payload,
+    req: HttpRequest,
+) -> HttpResponse {
+    let config = Arc::new(current_config(&state));
+    let language = request_language(&req, &config);
+    let mode = query.mode;
+
+    let mut reader = RowReader::new(batch_format(&req));
+    let mut rows = Vec::new();
+    let mut received = 0;
+    while let Some(chunk) = payload.next().await {
+        match chunk {
+            Ok(chunk) => {
+                received += chunk.len();
+                rows.extend(reader.push(&chunk));
+            }
+            Err(err) => {
+                let response = MSG {
+                    message: err.to_string(),
+                };
+                return HttpResponse::BadRequest().json(response);
+            }
+        }
+        if received > MAX_IMPORT_BYTES || rows.len() > MAX_IMPORT_ROWS {
+            return import_too_large();
+        }
+    }
+    rows.extend(reader.finish());
+    if rows.len() > MAX_IMPORT_ROWS {
+        return import_too_large();
+    }
+
+    let report = match import_rows(&db, config, rows, mode).await {
+        Ok(report) => report,
+        Err(err) => {
+            let response = MSG {
+                message: err.to_string(),
+            };
+            return HttpResponse::InternalServerError().json(response);
+        }
+    };
+
+    let body = import_json(&report, mode, language);
+    if report.aborted(mode) {
+        return HttpResponse::BadRequest().json(body);
+    }
+    HttpResponse::Ok().json(body)
+}
+
+/// Lists the `inserted` rows with their `index` and `id` and the `rejected` rows with their `index` and every failed field.
+fn import_json(report: &ImportReport, mode: ImportMode, language: Language) -> Value {
+    let inserted = report
+        .inserted
+        .iter()
+        .map(|(index, id)| serde_json::json!({ "index": index, "id": id }))
+        .collect::<Vec<_>>();
+    let rejected = report
+        .rejected
+        .iter()
+        .map(|(index, rejection)| match rejection {
+            Rejection::Invalid(failed) => invalid_row(*index, failed, language),
+            Rejection::Failed(reason) => {
+                serde_json::json!({ "index": index, "message": reason, "errors": [] })
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let message = if report.aborted(mode) {
+        format!("Nothing was imported, {} rows are invalid.", rejected.len())
+    } else {
+        format!("{} users imported.", inserted.len())
+    };
+    serde_json::json!({
+        "message": message,
+        "inserted": inserted,
+        "rejected": rejected,
+    })
+}
+
+/// The format of the response of a CSV import, selected with `?format=`
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    /// The same JSON as `POST /users:import`
+    #[default]
+    Json,
+    /// The error CSV as download
+    Csv,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CsvImportQuery {
+    #[serde(default)]
+    pub mode: ImportMode,
+    #[serde(default)]
+    pub format: ReportFormat,
+}
+
+/// **POST /users:import/csv** Endpoint to create the users of a CSV file
+///
+/// # Arguments
+/// * `db: Data<MongoRepo>` - The database connection
+/// * `query: Query<CsvImportQuery>` - The import `mode` like in `POST /users:import` and the `format` of the response
+/// * `multipart: Multipart` - The upload, the CSV file is the part named `file`
+/// * `req: HttpRequest` - The request, its `Accept-Language` header selects the language of the error messages
+///
+/// # Returns
+/// * `HttpResponse` - The response of `POST /users:import` with the `ignored_columns`,
+///   or with `?format=csv` the error CSV listing every rejected record as download
+#[post("/users:import/csv")]
+pub async fn import_csv(
+    state: Data<AppState>,
+    db: Data<MongoRepo>,
+    query: Query<CsvImportQuery>,
+    mut multipart: Multipart,
+    req: HttpRequest,
+) -> HttpResponse {
+    let config = Arc::new(current_config(&state));
+    let language = request_language(&req, &config);
+
+    let mut file = None;
+    while let Some(field) = multipart.next().await {
+        let mut field = match field {
+            Ok(field) => field,
+            Err(err) => {
+                let response = MSG {
+                    message: err.to_string(),
+                };
+                return HttpResponse::BadRequest().json(response);
+            }
+        };
+        if field.name() != Some("file") {
+            continue;
+        }
+        let mut bytes = Vec::new();
+        while let Some(chunk) = field.next().await {
+            match chunk {
+                Ok(chunk) => bytes.extend_from_slice(&chunk),
+                Err(err) => {
+                    let response = MSG {
+                        message: err.to_string(),
+                    };
+                    return HttpResponse::BadRequest().json(response);
+                }
+            }
+            if bytes.len() > MAX_IMPORT_BYTES {
+                return import_too_large();
+            }
+        }
+        file = Some(bytes);
+    }
+    let file = match file {
+        Some(file) => file,
+        None => {
+            let response = MSG {
+                message: "The upload has no part named `file`.".to_string(),
+            };
+            return HttpResponse::BadRequest().json(response);
+        }
+    };
+
+    let mut csv_rows = match read_csv(&file, &config) {
+        Ok(csv_rows) => csv_rows,
+        Err(err) => {
+            let response = MSG {
+                message: err.to_string(),
+            };
+            return HttpResponse::BadRequest().json(response);
+        }
+    };
+    if csv_rows.rows.len() > MAX_IMPORT_ROWS {
+        return import_too_large();
+    }
+    let rows = std::mem::take(&mut csv_rows.rows);
+    let report = match import_rows(&db, config.clone(), rows, query.mode).await {
+        Ok(report) => report,
+        Err(err) => {
+            let response = MSG {
+                message: err.to_string(),
+            };
+            return HttpResponse::InternalServerError().json(response);
+        }
+    };
+
+    let mut response = if report.aborted(query.mode) {
+        HttpResponse::BadRequest()
+    } else {
+        HttpResponse::Ok()
+    };
+    match query.format {
+        ReportFormat::Csv => match error_csv(&config, &csv_rows, &report, language) {
+            Ok(body) => response
+                .content_type("text/csv; charset=utf-8")
+                .insert_header((
+                    header::CONTENT_DISPOSITION,
+                    "attachment; filename=\"import-errors.csv\"",
+                ))
+                .body(body),
+            Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+        },
+        ReportFormat::Json => {
+            let mut body = import_json(&report, query.mode, language);
+            body["ignored_columns"] = Value::from(csv_rows.ignored_columns);
+            response.json(body)
+        }
+    }
+}
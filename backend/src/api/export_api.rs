@@ -0,0 +1,345 @@
+//! This module contains the export of the users
+use crate::{
+    current_config,
+    library::csv_import::spreadsheet_cell,
+    models::{db_models::User, db_models::MSG, xml_models::Config},
+    repository::mongodb_repo::MongoRepo,
+    AppState,
+};
+use actix_web::{
+    error::ErrorInternalServerError,
+    get,
+    http::header,
+    web::{Bytes, Data, Query},
+    HttpResponse,
+};
+use futures::StreamExt;
+use quick_xml::escape::escape;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// The format of an export, selected with `?format=`
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// Excel compatible CSV, UTF-8 with BOM and the delimiter of `<csvImport>`
+    Csv,
+    /// One user per line
+    #[default]
+    Ndjson,
+    /// A `<users>` document with one `<user>` element per user
+    Xml,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+    /// Comma separated fields to export, all declared fields if omitted
+    pub fields: Option<String>,
+    /// Writes `ahv_nr` as `756.****.****.72`
+    #[serde(default)]
+    pub mask_ahv: bool,
+}
+
+/// The exported fields: `_id` and every field of the config in the order of the config.
+fn exportable_fields(config: &Config) -> Vec<String> {
+    let mut fields = vec!["_id".to_string()];
+    fields.extend(
+        config
+            .validation_rules
+            .person
+            .field_rules()
+            .into_iter()
+            .map(|field| field.name),
+    );
+    fields
+}
+
+/// Turns a user into the selected fields, missing fields are left out.
+fn select_fields(
+    mut user: User,
+    config: &Config,
+    fields: &[String],
+    mask_ahv: bool,
+) -> Map<String, Value> {
+    user.refresh_age(config);
+    let id = user.id().map(|id| id.to_hex());
+    let masked = if mask_ahv {
+        Some(user.ahv_nr().masked())
+    } else {
+        None
+    };
+    let mut values = match serde_json::to_value(user) {
+        Ok(Value::Object(values)) => values,
+        _ => Map::new(),
+    };
+    values.insert("_id".to_string(), Value::from(id));
+    if let Some(masked) = masked {
+        values.insert("ahv_nr".to_string(), Value::from(masked));
+    }
+    fields
+        .iter()
+        .filter_map(|field| {
+            values
+                .remove(field)
+                .filter(|value| !value.is_null())
+                .map(|value| (field.clone(), value))
+        })
+        .collect()
+}
+
+/// Writes a value as text of a single cell, the members of objects like a Swiss location are separated by spaces.
+fn cell_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        Value::Object(members) => members
+            .values()
+            .map(cell_text)
+            .collect::<Vec<_>>()
+            .join(" "),
+        value => value.to_string(),
+    }
+}
+
+/// Writes a value as text of a CSV cell, formulas are neutralized so Excel shows them as text.
+fn csv_cell(value: &Value) -> String {
+    spreadsheet_cell(&cell_text(value)).into_owned()
+}
+
+/// Writes one CSV line with the given delimiter.
+fn csv_line(cells: &[String], delimiter: u8) -> Bytes {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(Vec::new());
+    // writing into memory can't fail
+    let _ = writer.write_record(cells);
+    Bytes::from(writer.into_inner().unwrap_or_default())
+}
+
+/// Writes a value as XML element, objects get one child element per member.
+fn write_xml(name: &str, value: &Value, xml: &mut String) {
+    xml.push_str(&format!("<{}>", name));
+    match value {
+        Value::Object(members) => {
+            for (member, value) in members {
+                write_xml(member, value, xml);
+            }
+        }
+        value => xml.push_str(&escape(cell_text(value).as_str())),
+    }
+    xml.push_str(&format!("</{}>", name));
+}
+
+/// **GET /users/export** Endpoint to export all users
+///
+/// # Arguments
+/// * `db: Data<MongoRepo>` - The database connection
+/// * `query: Query<ExportQuery>` - The `format` (`csv`, `ndjson` or `xml`), the comma separated `fields`
+///   and `mask_ahv=true` to hide the AHV numbers
+///
+/// # Returns
+/// * `HttpResponse` - The users as download, streamed from the database one by one.
+///   `400 Bad Request` if a selected field is not declared in the config
+#[get("/users/export")]
+pub async fn export_users(
+    state: Data<AppState>,
+    db: Data<MongoRepo>,
+    query: Query<ExportQuery>,
+) -> HttpResponse {
+    let config = current_config(&state);
+    let ExportQuery {
+        format,
+        fields,
+        mask_ahv,
+    } = query.into_inner();
+
+    let exportable = exportable_fields(&config);
+    let fields = match fields {
+        Some(fields) => fields
+            .split(',')
+            .map(|field| field.trim().to_string())
+            .filter(|field| !field.is_empty())
+            .collect::<Vec<_>>(),
+        None => exportable.clone(),
+    };
+    let unknown = fields
+        .iter()
+        .filter(|field| !exportable.contains(field))
+        .cloned()
+        .collect::<Vec<_>>();
+    if !unknown.is_empty() {
+        let response = MSG {
+            message: format!("Unknown fields: {}", unknown.join(", ")),
+        };
+        return HttpResponse::BadRequest().json(response);
+    }
+
+    let mut cursor = match db.user_cursor().await {
+        Ok(cursor) => cursor,
+        Err(err) => return HttpResponse::InternalServerError().body(err.to_string()),
+    };
+    let delimiter = config.csv_import.delimiter;
+
+    let body = async_stream::stream! {
+        match format {
+            // the BOM makes Excel read the file as UTF-8
+            ExportFormat::Csv => {
+                yield Ok(Bytes::from_static(b"\xEF\xBB\xBF"));
+                yield Ok(csv_line(&fields, delimiter));
+            }
+            ExportFormat::Xml => {
+                yield Ok(Bytes::from_static(b"<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<users>\n"));
+            }
+            ExportFormat::Ndjson => {}
+        }
+        while let Some(user) = cursor.next().await {
+            let user = match user {
+                Ok(user) => user,
+                Err(e) => {
+                    dbg!(&e);
+                    yield Err(ErrorInternalServerError("Error getting users"));
+                    return;
+                }
+            };
+            let values = select_fields(user, &config, &fields, mask_ahv);
+            let chunk = match format {
+                ExportFormat::Csv => {
+                    let cells = fields
+                        .iter()
+                        .map(|field| values.get(field).map(csv_cell).unwrap_or_default())
+                        .collect::<Vec<_>>();
+                    csv_line(&cells, delimiter)
+                }
+                ExportFormat::Ndjson => Bytes::from(format!("{}\n", Value::Object(values))),
+                ExportFormat::Xml => {
+                    let mut xml = String::from("  ");
+                    write_xml("user", &Value::Object(values), &mut xml);
+                    xml.push('\n');
+                    Bytes::from(xml)
+                }
+            };
+            yield Ok::<_, actix_web::Error>(chunk);
+        }
+        if format == ExportFormat::Xml {
+            yield Ok(Bytes::from_static(b"</users>\n"));
+        }
+    };
+
+    let (content_type, file_name) = match format {
+        ExportFormat::Csv => ("text/csv; charset=utf-8", "users.csv"),
+        ExportFormat::Ndjson => ("application/x-ndjson", "users.ndjson"),
+        ExportFormat::Xml => ("application/xml", "users.xml"),
+    };
+    HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", file_name),
+        ))
+        .streaming(body)
+}
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn user(config: &Config) -> User {
+        let document = json!({
+            "first_name": "Anna",
+            "last_name": "Muster",
+            "age": 30,
+            "pensum": 80,
+            "location": "Bern",
+            "occupation": "Informatikerin",
+            "ahv_nr": "756.1718.4457.72",
+        });
+        let document = document.as_object().cloned().unwrap_or_default();
+        User::from_document(config, document).unwrap().0
+    }
+
+    fn config() -> Config {
+        Config::from_xml(
+            r#"<config><validationRules><person>
+                <name type="string" min="1" max="50" />
+                <age type="integer" minValue="12" maxValue="120" />
+                <pensum type="integer" minValue="10" maxValue="100" />
+                <location type="string" min="2" max="55" />
+                <occupation type="string" min="2" max="55" />
+            </person></validationRules></config>"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn only_the_selected_fields_are_exported() {
+        let config = config();
+        let fields = [
+            "last_name".to_string(),
+            "ahv_nr".to_string(),
+            "email".to_string(),
+        ];
+        assert_eq!(
+            Value::Object(select_fields(user(&config), &config, &fields, false)),
+            json!({"last_name": "Muster", "ahv_nr": "756.1718.4457.72"})
+        );
+        assert_eq!(
+            Value::Object(select_fields(user(&config), &config, &fields, true)),
+            json!({"last_name": "Muster", "ahv_nr": "756.****.****.72"})
+        );
+        let exportable = exportable_fields(&config);
+        assert_eq!(exportable[..3], ["_id", "first_name", "last_name"]);
+        assert!(exportable.contains(&"date_of_birth".to_string()));
+    }
+
+    #[test]
+    fn csv_cells_are_escaped() {
+        let cells = [
+            "Muster; Anna".to_string(),
+            "say \"hi\"".to_string(),
+            "plain".to_string(),
+        ];
+        assert_eq!(
+            csv_line(&cells, b';'),
+            Bytes::from("\"Muster; Anna\";\"say \"\"hi\"\"\";plain\n")
+        );
+        assert_eq!(
+            csv_line(&cells, b','),
+            Bytes::from("Muster; Anna,\"say \"\"hi\"\"\",plain\n")
+        );
+        let location = json!({"plz": "3011", "locality": "Bern", "canton": "BE"});
+        assert_eq!(cell_text(&location), "3011 Bern BE");
+    }
+
+    #[test]
+    fn xml_elements_are_nested_and_escaped() {
+        let mut xml = String::new();
+        let user = json!({
+            "last_name": "Müller & <Söhne>",
+            "age": 30,
+            "location": {"plz": "3011", "locality": "Bern"},
+        });
+        write_xml("user", &user, &mut xml);
+        assert_eq!(
+            xml,
+            "<user><last_name>Müller &amp; &lt;Söhne&gt;</last_name><age>30</age>\
+             <location><plz>3011</plz><locality>Bern</locality></location></user>"
+        );
+    }
+
+    #[test]
+    fn formulas_are_exported_as_text() {
+        assert_eq!(
+            csv_cell(&json!("=HYPERLINK(\"http://x\")")),
+            "'=HYPERLINK(\"http://x\")"
+        );
+        assert_eq!(csv_cell(&json!("@SUM(A1)")), "'@SUM(A1)");
+        assert_eq!(csv_cell(&json!("+41791234567")), "+41791234567");
+        assert_eq!(csv_cell(&json!(-5)), "-5");
+        assert_eq!(csv_cell(&json!("Muster")), "Muster");
+    }
+}
@@ -1 +1,4 @@
+pub mod batch_api;
+pub mod export_api;
+pub mod occupation_api;
 pub mod user_api;
\ No newline at end of file
@@ -0,0 +1,31 @@
+//! This module contains the endpoints of the occupation code list
+use crate::{current_config, AppState};
+use actix_web::{
+    get,
+    web::{Data, Query},
+    HttpResponse,
+};
+use serde::Deserialize;
+
+/// How many occupations a search returns at most
+const MAX_SUGGESTIONS: usize = 20;
+
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    #[serde(default)]
+    pub q: String,
+}
+
+/// **GET /occupations?q=** Endpoint to search the occupation code list for autocompletion
+///
+/// # Arguments
+/// * `query: Query<SearchQuery>` - The prefix of a code or of a word of the label
+///
+/// # Returns
+/// * `HttpResponse` - The matching occupations as `[{"code": "2512", "label": "Softwareentwickler/in"}]`,
+///   an empty list if no code list is configured
+#[get("/occupations")]
+pub async fn search_occupations(state: Data<AppState>, query: Query<SearchQuery>) -> HttpResponse {
+    let config = current_config(&state);
+    HttpResponse::Ok().json(config.occupations.search(&query.q, MAX_SUGGESTIONS))
+}
@@ -1,86 +1,217 @@
 //! This module contains the endpoints
 use crate::{
-    models::db_models::{UpdatedUser, User, MSG},
+    current_config,
+    library::messages::{message, Language},
+    models::{
+        db_models::{UpdatedUser, User, MSG},
+        validation_models::ValidationReport,
+        xml_models::Config,
+    },
     repository::mongodb_repo::MongoRepo,
     AppState,
 };
 use actix_web::{
-    delete, get, post, put,
+    delete, get,
+    http::header,
+    post, put,
     web::{Data, Json, Path},
-    HttpResponse,
+    HttpRequest, HttpResponse,
 };
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::io::ErrorKind;
+
+/// Picks the language of the error messages.
+///
+/// The `Accept-Language` header of the request wins over `defaultSettings@language` from the config.
+pub(crate) fn request_language(req: &HttpRequest, config: &Config) -> Language {
+    req.headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(Language::from_accept_language)
+        .or_else(|| Language::from_code(&config.default_settings.language))
+        .unwrap_or_default()
+}
+
+/// Lists every failed field of a validation run as `{"message": ..., "errors": [...]}`.
+///
+/// Every error carries its `field`, the rule `code` and the rule `params`, plus a `message` in the requested language.
+/// The top level `message` joins all messages so clients which only display a single string still show every error.
+pub(crate) fn report_json(report: &ValidationReport, language: Language) -> Value {
+    let messages = report
+        .errors
+        .iter()
+        .map(|error| message(error, language))
+        .collect::<Vec<_>>();
+    let errors = report
+        .errors
+        .iter()
+        .zip(&messages)
+        .map(|(error, message)| {
+            let mut entry = serde_json::to_value(error).unwrap_or_default();
+            entry["message"] = serde_json::Value::from(message.as_str());
+            entry
+        })
+        .collect::<Vec<_>>();
+    serde_json::json!({
+        "message": messages.join(" "),
+        "errors": errors,
+    })
+}
+
+/// Builds the `400 Bad Request` response listing every failed field of a validation run.
+fn validation_failed(report: ValidationReport, language: Language) -> HttpResponse {
+    HttpResponse::BadRequest().json(report_json(&report, language))
+}
+
+/// Builds the body of the validate-only endpoints for an invalid payload: the failed fields and `"valid": false`.
+fn invalid_json(report: &ValidationReport, language: Language) -> Value {
+    let mut response = report_json(report, language);
+    response["valid"] = Value::from(false);
+    response
+}
+
+/// # Summary
+/// The body of `POST /user/validate/update`: the user as returned by `GET /user/{id}` and the fields to update.
+///
+/// The user is taken as plain JSON object, it is validated like a new user before the changes are checked.
+#[derive(Debug, Deserialize)]
+pub struct UpdateCheck {
+    pub user: Map<String, Value>,
+    pub changes: Map<String, Value>,
+}
+
+/// How often an update is retried if the user is changed concurrently
+const MAX_UPDATE_ATTEMPTS: usize = 3;
 
 /// **POST /user** Endpoint to create a new user
 /// # Arguments
 ///
 /// * `db: Data<MongoRepo>` - The database connection
-/// * `new_user: Json<User>` - The user data from the request body
+/// * `new_user: Json<Map<String, Value>>` - The user data from the request body
+/// * `req: HttpRequest` - The request, its `Accept-Language` header selects the language of the error messages
 ///
 /// # Returns
 ///
-/// * `HttpResponse` - The response with the status code and a message
+/// * `HttpResponse` - The response with the status code, a message and the fields which got their default, or every failed field on invalid data
 #[post("/user")]
 pub async fn create_user(
     db: Data<MongoRepo>,
-    new_user: Json<User>,
+    new_user: Json<Map<String, Value>>,
     state: Data<AppState>,
+    req: HttpRequest,
 ) -> HttpResponse {
-    let guard = state.valid_config.lock();
-    let config = match guard {
-        // get the config from the state it does not have clone trait
-        Ok(guard) => {
-            let config = guard;
-            config
-        }
-        Err(e) => {
-            dbg!(e);
-            return HttpResponse::InternalServerError().finish();
-        }
-    };
-    // Create a new user struct from the request data and validate it with the validate_user function from the middleware
-    let data = User::new(
-        config.clone(),
-        new_user.first_name.to_owned(),
-        new_user.last_name.to_owned(),
-        new_user.age.to_owned(),
-        new_user.pensum.to_owned(),
-        new_user.location.to_owned(),
-        new_user.occupation.to_owned(),
-        new_user.ahv_nr.to_owned(),
-    );
+    let config = current_config(&state);
+    let language = request_language(&req, &config);
+    // Create a new user struct from the request data, it is validated against the rules from the config
+    let data = User::from_document(&config, new_user.into_inner());
 
     match data {
-        Ok(user) => {
+        Ok((user, defaults_applied)) => {
             let result = db.create_user(user).await;
             match result {
                 Ok(user) => HttpResponse::Ok().json(
-                    serde_json::json!({"message" : "User successfully created!", "id" : user.inserted_id.as_object_id()}),
+                    serde_json::json!({"message" : "User successfully created!", "id" : user.inserted_id.as_object_id(), "defaults_applied" : defaults_applied}),
                 ),
                 Err(_) => {
                     HttpResponse::InternalServerError().finish()
                 }
             }
         }
-        Err(error_message) => {
-            let response = MSG {
-                message: error_message,
-            };
-            HttpResponse::BadRequest().json(response)
-        }
+        Err(report) => validation_failed(report, language),
     }
 }
 
+/// **POST /user/validate** Endpoint to check a new user without creating it
+///
+/// Runs the same defaults, normalization and rules as `POST /user` against the current config, nothing is stored.
+/// # Arguments
+///
+/// * `new_user: Json<Map<String, Value>>` - The user data from the request body
+/// * `req: HttpRequest` - The request, its `Accept-Language` header selects the language of the error messages
+///
+/// # Returns
+///
+/// * `HttpResponse` - Always `200 OK` with `valid` and every failed field,
+///   a valid user also comes with the normalized `user` and the fields which got their default
+#[post("/user/validate")]
+pub async fn validate_new_user(
+    new_user: Json<Map<String, Value>>,
+    state: Data<AppState>,
+    req: HttpRequest,
+) -> HttpResponse {
+    let config = current_config(&state);
+    let language = request_language(&req, &config);
+
+    let response = match User::from_document(&config, new_user.into_inner()) {
+        Ok((user, defaults_applied)) => serde_json::json!({
+            "valid": true,
+            "message": "",
+            "errors": [],
+            "user": user,
+            "defaults_applied": defaults_applied,
+        }),
+        Err(report) => invalid_json(&report, language),
+    };
+    HttpResponse::Ok().json(response)
+}
+
+/// **POST /user/validate/update** Endpoint to check an update without applying it
+///
+/// Runs the same normalization and rules as `PUT /user/{id}` against the current config.
+/// The current user is taken from the request body instead of the database, nothing is read or stored.
+/// If the current user itself is invalid, its failed fields are returned and the changes are not checked.
+/// # Arguments
+///
+/// * `check: Json<UpdateCheck>` - The current `user` and the `changes` from the request body
+/// * `req: HttpRequest` - The request, its `Accept-Language` header selects the language of the error messages
+///
+/// # Returns
+///
+/// * `HttpResponse` - Always `200 OK` with `valid` and every failed field,
+///   a valid update also comes with the normalized `changes`
+#[post("/user/validate/update")]
+pub async fn validate_user_update(
+    check: Json<UpdateCheck>,
+    state: Data<AppState>,
+    req: HttpRequest,
+) -> HttpResponse {
+    let config = current_config(&state);
+    let language = request_language(&req, &config);
+    let UpdateCheck { user, changes } = check.into_inner();
+    let user = match User::from_document(&config, user) {
+        Ok((user, _)) => user,
+        Err(report) => return HttpResponse::Ok().json(invalid_json(&report, language)),
+    };
+
+    let response = match UpdatedUser::from_document(&config, &user, changes) {
+        Ok(changes) => serde_json::json!({
+            "valid": true,
+            "message": "",
+            "errors": [],
+            "changes": changes,
+        }),
+        Err(report) => invalid_json(&report, language),
+    };
+    HttpResponse::Ok().json(response)
+}
+
 /// **GET /user/{id}** Endpoint to get a user by id
 ///
 /// # Arguments
 /// * `db: Data<MongoRepo>` - The database connection
+/// * `state: Data<AppState>` - The config, it declares from which date the age is derived
 /// * `path: Path<String>` - The id of the user
 ///
 /// # Returns
 ///
 /// * `HttpResponse` - The response with the status code and a message of the retrieved user
 #[get("/user/{id}")]
-pub async fn get_user(db: Data<MongoRepo>, path: Path<String>) -> HttpResponse {
+pub async fn get_user(
+    db: Data<MongoRepo>,
+    state: Data<AppState>,
+    path: Path<String>,
+) -> HttpResponse {
     let id = path.into_inner();
     if id.is_empty() {
         return HttpResponse::BadRequest().body("invalid ID");
@@ -88,7 +219,10 @@ pub async fn get_user(db: Data<MongoRepo>, path: Path<String>) -> HttpResponse {
     let user_detail = db.get_user(&id).await;
 
     match user_detail {
-        Ok(user) => HttpResponse::Ok().json(user),
+        Ok(mut user) => {
+            user.refresh_age(&current_config(&state));
+            HttpResponse::Ok().json(user)
+        }
         Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
     }
 }
@@ -98,28 +232,20 @@ pub async fn get_user(db: Data<MongoRepo>, path: Path<String>) -> HttpResponse {
 /// # Arguments
 /// * `db: Data<MongoRepo>` - The database connection
 /// * `path: Path<String>` - The id of the user
-/// * `new_user: Json<UpdatedUser>` - The user data from the request body
+/// * `new_user: Json<Map<String, Value>>` - The fields to update from the request body
+/// * `req: HttpRequest` - The request, its `Accept-Language` header selects the language of the error messages
 /// # Returns
-/// * `HttpResponse` - The response with the status code and a message
+/// * `HttpResponse` - The response with the status code and a message, `409 Conflict` if the user kept changing concurrently
 #[put("/user/{id}")]
 pub async fn update_user(
     state: Data<AppState>,
     db: Data<MongoRepo>,
     path: Path<String>,
-    new_user: Json<UpdatedUser>,
+    new_user: Json<Map<String, Value>>,
+    req: HttpRequest,
 ) -> HttpResponse {
-    let guard = state.valid_config.lock();
-    let config = match guard {
-        // get the config from the state it does not have clone trait
-        Ok(guard) => {
-            let config = guard;
-            config
-        }
-        Err(e) => {
-            dbg!(e);
-            return HttpResponse::InternalServerError().finish();
-        }
-    };
+    let config = current_config(&state);
+    let language = request_language(&req, &config);
 
     let id = path.into_inner();
     if id.is_empty() {
@@ -130,52 +256,55 @@ pub async fn update_user(
             HttpResponse::BadRequest().json(response)
         };
     };
-    let data = match UpdatedUser::new(
-        config.clone(),
-        new_user.first_name.to_owned(),
-        new_user.last_name.to_owned(),
-        new_user.age.to_owned(),
-        new_user.pensum.to_owned(),
-        new_user.location.to_owned(),
-        new_user.occupation.to_owned(),
-        new_user.ahv_nr.to_owned(),
-    ) {
-        Ok(data) => data,
-        Err(err) => {
-            return {
-                let response = MSG { message: err };
-                HttpResponse::BadRequest().json(response)
-            }
-        }
-    };
+    let document = new_user.into_inner();
 
-    let update_result = db.update_user(&id, data).await;
+    // Read, validate and write again if the user was changed concurrently in between
+    for _ in 0..MAX_UPDATE_ATTEMPTS {
+        let (stored, snapshot) = match db.get_user_snapshot(&id).await {
+            Ok(stored) => stored,
+            Err(err) => {
+                let response = MSG {
+                    message: err.to_string(),
+                };
+                return match err.kind() {
+                    ErrorKind::NotFound => HttpResponse::NotFound().json(response),
+                    _ => HttpResponse::BadRequest().json(response),
+                };
+            }
+        };
+        let data = match UpdatedUser::from_document(&config, &stored, document.clone()) {
+            Ok(data) => data,
+            Err(report) => return validation_failed(report, language),
+        };
 
-    match update_result {
-        Ok(res) => {
-            if res.modified_count == 1 {
-                return {
+        match db.update_user(&id, &snapshot, data).await {
+            Ok(res) if res.matched_count == 0 => continue,
+            Ok(res) => {
+                return if res.modified_count == 1 {
                     let response = MSG {
                         message: "User successfully updated!".to_string(),
                     };
                     HttpResponse::Ok().json(response)
-                };
-            } else {
-                return {
+                } else {
                     let response = MSG {
                         message: "Nothing changed.".to_string(),
                     };
                     HttpResponse::NotFound().json(response)
                 };
             }
-        }
-        Err(err) => {
-            let response = MSG {
-                message: err.to_string(),
-            };
-            HttpResponse::BadRequest().json(response)
+            Err(err) => {
+                let response = MSG {
+                    message: err.to_string(),
+                };
+                return HttpResponse::BadRequest().json(response);
+            }
         }
     }
+
+    let response = MSG {
+        message: "User was changed concurrently, please try again.".to_string(),
+    };
+    HttpResponse::Conflict().json(response)
 }
 
 /// **DELETE /user/{id}** Endpoint to delete a user by id
@@ -196,19 +325,15 @@ pub async fn delete_user(db: Data<MongoRepo>, path: Path<String>) -> HttpRespons
     match result {
         Ok(res) => {
             if res.deleted_count == 1 {
-                return {
-                    let response = MSG {
-                        message: "User successfully deleted!".to_string(),
-                    };
-                    HttpResponse::Ok().json(response)
+                let response = MSG {
+                    message: "User successfully deleted!".to_string(),
                 };
+                HttpResponse::Ok().json(response)
             } else {
-                return {
-                    let response = MSG {
-                        message: "User with specified ID not found!".to_string(),
-                    };
-                    HttpResponse::NotFound().json(response)
+                let response = MSG {
+                    message: "User with specified ID not found!".to_string(),
                 };
+                HttpResponse::NotFound().json(response)
             }
         }
         Err(err) => {
@@ -224,14 +349,19 @@ pub async fn delete_user(db: Data<MongoRepo>, path: Path<String>) -> HttpRespons
 ///
 /// # Arguments
 /// * `db: Data<MongoRepo>` - The database connection
+/// * `state: Data<AppState>` - The config, it declares from which date the age is derived
 /// # Returns
 /// * `HttpResponse` - The response with the status code and a message
 #[get("/users")]
-pub async fn get_all_users(db: Data<MongoRepo>) -> HttpResponse {
+pub async fn get_all_users(db: Data<MongoRepo>, state: Data<AppState>) -> HttpResponse {
     let users = db.get_all_users().await;
+    let config = current_config(&state);
 
     match users {
-        Ok(users) => HttpResponse::Ok().json(users),
+        Ok(mut users) => {
+            users.iter_mut().for_each(|user| user.refresh_age(&config));
+            HttpResponse::Ok().json(users)
+        }
         Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
     }
 }
@@ -0,0 +1,292 @@
+//! This module contains the batch validation. Many users are validated at once against one snapshot of the config,
+//! the rows are read from a JSON array or from NDJSON with one user per line.
+use crate::{
+    models::{
+        db_models::User,
+        validation_models::{ValidationReport, Violation},
+        xml_models::Config,
+    },
+    repository::mongodb_repo::MongoRepo,
+};
+use actix_web::web;
+use mongodb::bson::oid::ObjectId;
+use rayon::prelude::*;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::{
+    io::{Error, ErrorKind},
+    sync::Arc,
+};
+
+/// The field name of errors which concern a whole row, e.g. a line which is no JSON object
+pub const ROW_FIELD: &str = "row";
+
+/// A row of a batch: its position in the payload and the user data, or the report of an unreadable row.
+pub type Row = (usize, Result<Map<String, Value>, ValidationReport>);
+
+/// A validated row: its position in the payload and the user with the fields which got their default, or every failed check.
+pub type RowResult = (usize, Result<(User, Vec<String>), ValidationReport>);
+
+/// The format of a batch payload
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BatchFormat {
+    /// A JSON array of users
+    JsonArray,
+    /// One user per line
+    Ndjson,
+}
+
+/// # Summary
+/// Splits a payload which arrives in chunks into rows.
+///
+/// NDJSON rows are available as soon as their line is complete, a JSON array is parsed when the payload is complete.
+/// Blank lines are skipped and don't count as rows.
+pub struct RowReader {
+    format: BatchFormat,
+    buffer: Vec<u8>,
+    next_index: usize,
+}
+
+impl RowReader {
+    pub fn new(format: BatchFormat) -> RowReader {
+        RowReader {
+            format,
+            buffer: Vec::new(),
+            next_index: 0,
+        }
+    }
+
+    /// Adds a chunk of the payload and returns the rows which are complete.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<Row> {
+        self.buffer.extend_from_slice(chunk);
+        if self.format == BatchFormat::JsonArray {
+            return Vec::new();
+        }
+        let end = match self.buffer.iter().rposition(|byte| *byte == b'\n') {
+            Some(end) => end + 1,
+            None => return Vec::new(),
+        };
+        let lines = self.buffer.drain(..end).collect::<Vec<_>>();
+        self.parse_lines(&lines)
+    }
+
+    /// Number of bytes which are not yet split into rows
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Returns the remaining rows at the end of the payload.
+    ///
+    /// A JSON array which can't be parsed is reported as a single row with `bad_format`.
+    pub fn finish(&mut self) -> Vec<Row> {
+        let rest = std::mem::take(&mut self.buffer);
+        match self.format {
+            BatchFormat::Ndjson => self.parse_lines(&rest),
+            BatchFormat::JsonArray => match serde_json::from_slice::<Vec<Value>>(&rest) {
+                Ok(values) => values.into_iter().map(|value| self.row(value)).collect(),
+                Err(_) => vec![self.row(Value::Null)],
+            },
+        }
+    }
+
+    fn parse_lines(&mut self, lines: &[u8]) -> Vec<Row> {
+        lines
+            .split(|byte| *byte == b'\n')
+            .filter(|line| !line.trim_ascii().is_empty())
+            .map(|line| {
+                let value = serde_json::from_slice(line).unwrap_or(Value::Null);
+                self.row(value)
+            })
+            .collect()
+    }
+
+    /// Numbers the row, values which are no JSON object are reported with `bad_format`.
+    fn row(&mut self, value: Value) -> Row {
+        let index = self.next_index;
+        self.next_index += 1;
+        match value {
+            Value::Object(user) => (index, Ok(user)),
+            _ => (index, Err(unreadable_row())),
+        }
+    }
+}
+
+/// The report of a row which can't be read, `bad_format` of the whole row.
+pub fn unreadable_row() -> ValidationReport {
+    let mut report = ValidationReport::default();
+    report.add(ROW_FIELD, Violation::BadFormat);
+    report
+}
+
+///# Validates rows in parallel
+///
+/// Every row gets the same defaults, normalization and rules as `POST /user` against the same config.
+/// The results keep the order of the rows.
+pub fn validate_rows(config: &Config, rows: Vec<Row>) -> Vec<RowResult> {
+    rows.into_par_iter()
+        .map(|(index, row)| {
+            (
+                index,
+                row.and_then(|user| User::from_document(config, user)),
+            )
+        })
+        .collect()
+}
+
+/// How an import treats invalid rows, selected with `?mode=`
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    /// Nothing is stored if any row is invalid or can't be written
+    #[default]
+    Atomic,
+    /// The valid rows are stored, the invalid ones are rejected
+    BestEffort,
+}
+
+/// Why a row was not imported
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rejection {
+    /// The row failed the validation
+    Invalid(ValidationReport),
+    /// The row was valid but the database refused it
+    Failed(String),
+}
+
+/// # Summary
+/// The outcome of an import: the ids of the stored rows and the reasons of the rejected ones, both by row index.
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub inserted: Vec<(usize, ObjectId)>,
+    pub rejected: Vec<(usize, Rejection)>,
+}
+
+impl ImportReport {
+    /// `true` if an atomic import stopped because of invalid rows
+    pub fn aborted(&self, mode: ImportMode) -> bool {
+        mode == ImportMode::Atomic && !self.rejected.is_empty()
+    }
+}
+
+///# Validates and stores rows
+///
+/// All rows are validated in parallel against `config`, then the valid ones are written with one [`MongoRepo::import_users`].
+/// In [`ImportMode::Atomic`] nothing is written if a row is invalid.
+/// Returns an error if the database fails as a whole, in atomic mode also if a single row can't be written.
+pub async fn import_rows(
+    db: &MongoRepo,
+    config: Arc<Config>,
+    rows: Vec<Row>,
+    mode: ImportMode,
+) -> Result<ImportReport, Error> {
+    let results = web::block(move || validate_rows(&config, rows))
+        .await
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, "Error validating users"))?;
+
+    let mut report = ImportReport::default();
+    let mut valid = Vec::new();
+    for (index, result) in results {
+        match result {
+            Ok((user, _)) => valid.push((index, user)),
+            Err(failed) => report.rejected.push((index, Rejection::Invalid(failed))),
+        }
+    }
+    if report.aborted(mode) {
+        return Ok(report);
+    }
+
+    let (indexes, users): (Vec<_>, Vec<_>) = valid.into_iter().unzip();
+    let written = db.import_users(users, mode == ImportMode::Atomic).await?;
+    for (index, result) in indexes.into_iter().zip(written) {
+        match result {
+            Ok(id) => report.inserted.push((index, id)),
+            Err(reason) => report.rejected.push((index, Rejection::Failed(reason))),
+        }
+    }
+    report.rejected.sort_by_key(|(index, _)| *index);
+    Ok(report)
+}
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ndjson_rows_are_split_across_chunks() {
+        let mut reader = RowReader::new(BatchFormat::Ndjson);
+        assert_eq!(reader.push(b"{\"age\": 1}\n{\"ag").len(), 1);
+        let rows = reader.push(b"e\": 2}\n\n[3]\n");
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].0, 1);
+        assert!(rows[1].1.is_err());
+        let rows = reader.finish();
+        assert!(rows.is_empty());
+    }
+
+    #[test]
+    fn json_arrays_are_read_when_complete() {
+        let mut reader = RowReader::new(BatchFormat::JsonArray);
+        assert!(reader.push(b"[{\"age\": 1}, ").is_empty());
+        assert!(reader.push(b"2]").is_empty());
+        assert_eq!(reader.buffered(), 15);
+        let rows = reader.finish();
+        assert_eq!(rows.len(), 2);
+        assert!(rows[0].1.is_ok());
+        assert_eq!(rows[1].0, 1);
+        assert!(rows[1].1.is_err());
+    }
+
+    fn rows(documents: Vec<Value>) -> Vec<Row> {
+        let mut reader = RowReader::new(BatchFormat::JsonArray);
+        reader.push(Value::Array(documents).to_string().as_bytes());
+        reader.finish()
+    }
+
+    #[actix_web::test]
+    async fn atomic_imports_stop_at_an_invalid_row() {
+        let config = Config::from_xml(&std::fs::read_to_string("valid.xml").unwrap()).unwrap();
+        // no server is needed, the connection is only opened by the first write
+        let db = MongoRepo::connect("mongodb://localhost:27017")
+            .await
+            .unwrap();
+        let valid = serde_json::json!({
+            "first_name": "Anna",
+            "last_name": "Muster",
+            "age": 30,
+            "location": "Bern",
+            "occupation": "Informatikerin",
+            "ahv_nr": "756.1718.4457.72",
+        });
+        let invalid = serde_json::json!({"first_name": "Beat"});
+
+        let report = import_rows(
+            &db,
+            Arc::new(config.clone()),
+            rows(vec![valid.clone(), invalid.clone(), valid]),
+            ImportMode::Atomic,
+        )
+        .await
+        .unwrap();
+        assert!(report.aborted(ImportMode::Atomic));
+        assert!(report.inserted.is_empty());
+        assert_eq!(report.rejected.len(), 1);
+        assert!(matches!(&report.rejected[0], (1, Rejection::Invalid(_))));
+
+        // without valid rows nothing is written in best effort mode either
+        let report = import_rows(
+            &db,
+            Arc::new(config),
+            rows(vec![invalid, Value::Null]),
+            ImportMode::BestEffort,
+        )
+        .await
+        .unwrap();
+        assert!(!report.aborted(ImportMode::BestEffort));
+        assert_eq!(report.rejected.len(), 2);
+        assert!(
+            matches!(&report.rejected[1], (1, Rejection::Invalid(row)) if *row == unreadable_row())
+        );
+    }
+}
@@ -0,0 +1,326 @@
+//! This module contains the CSV import. HR exports are semicolon separated and encoded in Windows-1252
+//! or UTF-8 with BOM, their columns are mapped to the fields of a user with the `<csvImport>` element of the config.
+use crate::{
+    library::{
+        batch::{import_rows, unreadable_row, ImportMode, ImportReport, Rejection, Row},
+        lib::comparable,
+        messages::{message, Language},
+    },
+    models::xml_models::Config,
+    repository::mongodb_repo::MongoRepo,
+};
+use encoding_rs::WINDOWS_1252;
+use serde_json::{Map, Value};
+use std::{
+    borrow::Cow,
+    io::{Error, ErrorKind},
+    sync::Arc,
+};
+
+/// Byte order mark of UTF-8, Excel writes it to recognize the encoding
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// # Summary
+/// The rows of a CSV file together with the original records, to list the rejected ones in the error CSV.
+#[derive(Debug, Default)]
+pub struct CsvRows {
+    /// The headers of the file
+    pub headers: Vec<String>,
+    /// The line number in the file and the values of every record, in the order of `rows`
+    pub records: Vec<(u64, Vec<String>)>,
+    /// The users mapped from the records, empty cells are omitted
+    pub rows: Vec<Row>,
+    /// Headers which map to no field, their columns are not imported
+    pub ignored_columns: Vec<String>,
+}
+
+///# Decodes a CSV file
+///
+/// UTF-8 with or without BOM is read as is, everything else is read as Windows-1252.
+pub fn decode(bytes: &[u8]) -> Cow<'_, str> {
+    let bytes = bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes);
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Cow::Borrowed(text),
+        Err(_) => WINDOWS_1252.decode_without_bom_handling(bytes).0,
+    }
+}
+
+///# Reads a CSV file
+///
+/// Decodes the file, maps every column with the `<csvImport>` element of the config and turns every record into a row.
+/// Records with more or less cells than the header are reported with `bad_format`.
+/// Returns an error if the header can't be read.
+pub fn read_csv(bytes: &[u8], config: &Config) -> Result<CsvRows, Error> {
+    let text = decode(bytes);
+    let settings = &config.csv_import;
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(settings.delimiter)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+
+    let headers = match reader.headers() {
+        Ok(headers) => headers
+            .iter()
+            .map(|header| header.trim().to_string())
+            .collect::<Vec<_>>(),
+        Err(_) => {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Error reading CSV header",
+            ))
+        }
+    };
+    let fields = config
+        .validation_rules
+        .person
+        .field_rules()
+        .into_iter()
+        .map(|field| field.name)
+        .collect::<Vec<_>>();
+    let targets = headers
+        .iter()
+        .map(|header| {
+            let header = comparable(header);
+            settings
+                .columns
+                .iter()
+                .find(|column| comparable(&column.header) == header)
+                .map(|column| column.field.clone())
+                .or_else(|| {
+                    fields
+                        .iter()
+                        .find(|field| comparable(field) == header)
+                        .cloned()
+                })
+        })
+        .collect::<Vec<_>>();
+
+    let mut csv_rows = CsvRows {
+        ignored_columns: headers
+            .iter()
+            .zip(&targets)
+            .filter(|(_, target)| target.is_none())
+            .map(|(header, _)| header.clone())
+            .collect(),
+        headers,
+        ..CsvRows::default()
+    };
+    for (index, record) in reader.records().enumerate() {
+        let record = match record {
+            Ok(record) => record,
+            Err(_) => {
+                csv_rows.records.push((index as u64 + 2, Vec::new()));
+                csv_rows.rows.push((index, Err(unreadable_row())));
+                continue;
+            }
+        };
+        let line = record
+            .position()
+            .map_or(index as u64 + 2, |position| position.line());
+        let values = record.iter().map(str::to_string).collect::<Vec<_>>();
+
+        let row = if values.len() == csv_rows.headers.len() {
+            let mut user = Map::new();
+            for (target, value) in targets.iter().zip(&values) {
+                if let Some(field) = target {
+                    if !value.trim().is_empty() {
+                        user.insert(field.clone(), Value::from(value.as_str()));
+                    }
+                }
+            }
+            Ok(user)
+        } else {
+            Err(unreadable_row())
+        };
+        csv_rows.records.push((line, values));
+        csv_rows.rows.push((index, row));
+    }
+    Ok(csv_rows)
+}
+
+///# Neutralizes a spreadsheet formula
+///
+/// Excel runs cells starting with `=`, `@`, `+` or `-` as formula, also after a leading tab or carriage return.
+/// Such cells get a leading `'`, so they are shown as text. Numbers like `-5` or E.164 phone numbers
+/// like `+41791234567` are kept as they are.
+pub fn spreadsheet_cell(text: &str) -> Cow<'_, str> {
+    let is_number = |rest: &str| {
+        !rest.is_empty()
+            && rest.chars().all(|c| c.is_ascii_digit() || c == '.')
+            && rest.matches('.').count() <= 1
+    };
+    let formula = match text.chars().next() {
+        Some('=' | '@' | '\t' | '\r') => true,
+        Some('+' | '-') => !is_number(&text[1..]),
+        _ => false,
+    };
+    if formula {
+        Cow::Owned(format!("'{}", text))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+///# Writes the error CSV
+///
+/// Lists every rejected record with its line in the file, its original values and the reasons in `language`.
+/// The file is UTF-8 with BOM and uses the delimiter of the config, so Excel opens it directly.
+/// Formulas of the uploaded cells are neutralized with [`spreadsheet_cell`].
+pub fn error_csv(
+    config: &Config,
+    csv_rows: &CsvRows,
+    report: &ImportReport,
+    language: Language,
+) -> Result<Vec<u8>, Error> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(config.csv_import.delimiter)
+        .flexible(true)
+        .from_writer(UTF8_BOM.to_vec());
+
+    let mut header = vec!["line".to_string()];
+    header.extend(
+        csv_rows
+            .headers
+            .iter()
+            .map(|text| spreadsheet_cell(text).into_owned()),
+    );
+    header.push("errors".to_string());
+    writer.write_record(&header)?;
+
+    for (index, rejection) in &report.rejected {
+        let (line, values) = match csv_rows.records.get(*index) {
+            Some(record) => record,
+            None => continue,
+        };
+        let reasons = match rejection {
+            Rejection::Invalid(failed) => failed
+                .errors
+                .iter()
+                .map(|error| message(error, language))
+                .collect::<Vec<_>>()
+                .join(" "),
+            Rejection::Failed(reason) => reason.clone(),
+        };
+        let mut record = vec![line.to_string()];
+        record.extend(
+            values
+                .iter()
+                .map(|text| spreadsheet_cell(text).into_owned()),
+        );
+        record.push(reasons);
+        writer.write_record(&record)?;
+    }
+    writer
+        .into_inner()
+        .map_err(|_| Error::new(ErrorKind::InvalidData, "Error writing error CSV"))
+}
+
+///# Imports a CSV file from the command line
+///
+/// Reads the file at `path`, imports its rows like `POST /users:import/csv` and writes the error CSV to `errors_path`
+/// if a row is rejected. Returns a summary for the console.
+pub async fn import_file(
+    db: &MongoRepo,
+    config: Config,
+    path: &str,
+    errors_path: &str,
+    mode: ImportMode,
+) -> Result<String, Error> {
+    let bytes = std::fs::read(path)?;
+    let mut csv_rows = read_csv(&bytes, &config)?;
+    let rows = std::mem::take(&mut csv_rows.rows);
+    let language = Language::from_code(&config.default_settings.language).unwrap_or_default();
+    let config = Arc::new(config);
+    let report = import_rows(db, config.clone(), rows, mode).await?;
+
+    let mut summary = String::new();
+    if !csv_rows.ignored_columns.is_empty() {
+        summary += &format!("Ignored columns: {}\n", csv_rows.ignored_columns.join(", "));
+    }
+    if report.aborted(mode) {
+        summary += "Nothing was imported.\n";
+    } else {
+        summary += &format!("{} users imported.\n", report.inserted.len());
+    }
+    if !report.rejected.is_empty() {
+        std::fs::write(
+            errors_path,
+            error_csv(&config, &csv_rows, &report, language)?,
+        )?;
+        summary += &format!(
+            "{} rows rejected, see {}.\n",
+            report.rejected.len(),
+            errors_path
+        );
+    }
+    Ok(summary)
+}
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{
+        validation_models::{ValidationReport, Violation},
+        xml_models::CsvColumn,
+    };
+    use serde_json::json;
+
+    #[test]
+    fn csv_files_are_decoded_and_mapped() {
+        let mut config = Config::default();
+        config.csv_import.columns.push(CsvColumn {
+            header: "Vorname".to_string(),
+            field: "first_name".to_string(),
+        });
+        // "Jürg" in Windows-1252
+        let csv_rows = read_csv(
+            b"vorname;Location;Personalnummer\nJ\xFCrg;Z\xFCrich;17\n",
+            &config,
+        )
+        .unwrap();
+        assert_eq!(csv_rows.ignored_columns, vec!["Personalnummer".to_string()]);
+        let user = csv_rows.rows[0].1.clone().unwrap();
+        assert_eq!(user["first_name"], json!("Jürg"));
+        assert_eq!(user["location"], json!("Zürich"));
+    }
+
+    #[test]
+    fn formulas_are_neutralized() {
+        for formula in [
+            "=HYPERLINK(\"http://x\")",
+            "@SUM(A1)",
+            "+1+1",
+            "-2+3",
+            "\t=1",
+            "\r=1",
+        ] {
+            assert_eq!(spreadsheet_cell(formula), format!("'{}", formula));
+        }
+        for text in ["Muster", "+41791234567", "-5", "-2.5", "a=b", ""] {
+            assert_eq!(spreadsheet_cell(text), text);
+        }
+    }
+
+    #[test]
+    fn the_error_csv_lists_the_rejected_records() {
+        let csv_rows = read_csv(
+            b"first_name;last_name\n=HYPERLINK(\"http://x\");Muster\n",
+            &Config::default(),
+        )
+        .unwrap();
+        let mut failed = ValidationReport::default();
+        failed.add("age", Violation::Required);
+        let report = ImportReport {
+            inserted: Vec::new(),
+            rejected: vec![(0, Rejection::Invalid(failed))],
+        };
+        let bytes = error_csv(&Config::default(), &csv_rows, &report, Language::En).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert_eq!(
+            text.trim_start_matches('\u{feff}'),
+            "line;first_name;last_name;errors\n2;\"'=HYPERLINK(\"\"http://x\"\")\";Muster;Age is required.\n"
+        );
+    }
+}
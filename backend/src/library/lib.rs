@@ -1,239 +1,662 @@
 //! This module contains the validation functions
-use crate::models::xml_models::Config;
-use regex::Regex;
+use crate::{
+    library::rule_engine::evaluate,
+    models::{
+        domain_models::{AhvNumber, EmailAddress, Iban, PhoneNumber, Uid},
+        validation_models::{ValidationReport, Violation},
+        xml_models::{Config, Email, LengthUnit, Phone},
+    },
+};
+use chrono::{Datelike, Local, NaiveDate};
+use serde_json::{Map, Value};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+use unicode_segmentation::UnicodeSegmentation;
 
 ///# Validates the user.
 ///
+/// Every declared field and every cross-field rule is checked by the [rule engine](crate::library::rule_engine),
+/// all failed checks are collected in the returned [`ValidationReport`].
+///
 ///## Arguments
 ///
-/// * `config: Config` - The config struct with the validation rules
-/// * `first_name: &str` - The first name of the user
-/// * `last_name: &str` - The last name of the user
-/// * `age: &str` - The age of the user
-/// * `pensum: &str` - The pensum of the user
-/// * `location: &str` - The location of the user
-/// * `occupation: &str` - The occupation of the user
-/// * `ahv_nr: &str` - The ahv number of the user
-pub fn validate_user(
-    config: Config,
-    first_name: &str,
-    last_name: &str,
-    age: &str,
-    pensum: &str,
-    location: &str,
-    occupation: &str,
-    ahv_nr: &str,
-) -> Result<(), String> {
-    // Extract the configs for the user
-    let name_config = &config.validation_rules.person.name;
-    let age_config = &config.validation_rules.person.age;
-    let pensum_config = &config.validation_rules.person.pensum;
-    println!("{:?}", config);
-    // check first_name
-    if first_name.len() < name_config.min as usize || first_name.len() > name_config.max as usize {
-        return Err(format!(
-            "Vorname muss mindestens {} Buchstaben lang sein und nicht länger als {} Zeichen.",
-            name_config.min, name_config.max
-        ));
-    }
-
-    // check last_name
-    if last_name.len() < name_config.min as usize || last_name.len() > name_config.max as usize {
-        return Err(format!(
-            "Nachname muss mindestens {} Buchstaben lang sein und nicht länger als {} Zeichen.",
-            name_config.min, name_config.max
-        ));
-    }
-
-    let age_int: u32 = match age.parse::<u32>() {
-        Ok(age_int) => age_int,
-        Err(_) => return Err("Ungültiges Alter.".to_string()),
+/// * `config: &Config` - The config struct with the validation rules
+/// * `user: &Map<String, Value>` - The user as JSON object, every required field has to be present
+pub fn validate_user(config: &Config, user: &Map<String, Value>) -> Result<(), ValidationReport> {
+    evaluate(config, user)
+}
+
+///# Validate optional fields for updates
+///
+/// Applies the update to the stored user and validates the result like a new user,
+/// so no rule can be bypassed by updating only some of the fields.
+/// All failed checks are collected in the returned [`ValidationReport`].
+///
+///## Arguments
+///
+/// * `config: &Config` - The config struct with the validation rules
+/// * `stored: &Map<String, Value>` - The user as it is currently stored
+/// * `update: &Map<String, Value>` - The fields to update
+pub fn validate_update(
+    config: &Config,
+    stored: &Map<String, Value>,
+    update: &Map<String, Value>,
+) -> Result<(), ValidationReport> {
+    let mut merged = stored.clone();
+    merged.extend(update.clone());
+    validate_user(config, &merged)
+}
+
+///# Measures the length of a text
+///
+/// `"Zoë"` has 3 characters but 4 bytes, `"👨‍👩‍👧"` is one grapheme cluster made of 5 characters.
+pub fn text_length(value: &str, unit: LengthUnit) -> usize {
+    match unit {
+        LengthUnit::Bytes => value.len(),
+        LengthUnit::Chars => value.chars().count(),
+        LengthUnit::Graphemes => value.graphemes(true).count(),
+    }
+}
+
+///# Parses a date
+///
+/// Accepts the ISO notation `1990-05-17` and the Swiss notation `17.05.1990`.
+pub fn parse_date(value: &str) -> Option<NaiveDate> {
+    let value = value.trim();
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(value, "%d.%m.%Y"))
+        .ok()
+}
+
+///# Calculates the age on a given day
+///
+/// Someone born on February 29 turns a year older on March 1 in years without February 29.
+pub fn age_on(date_of_birth: NaiveDate, today: NaiveDate) -> u32 {
+    let years = today.year() - date_of_birth.year();
+    let had_birthday = (today.month(), today.day()) >= (date_of_birth.month(), date_of_birth.day());
+    let years = if had_birthday { years } else { years - 1 };
+    years.max(0) as u32
+}
+
+///# Today's date in local time
+pub fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+///# Prepares a text for a tolerant comparison
+///
+/// Lower case without accents and with single spaces, so `zurich` matches `Zürich`.
+pub fn comparable(text: &str) -> String {
+    text.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .collect::<String>()
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+///# Validate the ahv number
+///
+/// This is the algorithm for validating the ahv number.
+/// Accepts the number with dots, spaces, dashes or without separators, e.g. `756.1718.4457.72`, `7561718445772`
+/// or `756-1718-4457-72`, and returns it in the canonical notation `756.1718.4457.72`.
+/// Returns `bad_format` if the value isn't a number of 13 digits starting with `756` and `bad_checksum` if the check digit is wrong.
+pub fn validate_ahv(ahv_nr: &str) -> Result<AhvNumber, Violation> {
+    // only digits and separators are allowed
+    if !ahv_nr
+        .chars()
+        .all(|c| c.is_ascii_digit() || c.is_whitespace() || c == '.' || c == '-')
+    {
+        return Err(Violation::BadFormat);
+    }
+    let digits = ahv_nr
+        .chars()
+        .filter(|c| c.is_ascii_digit())
+        .collect::<String>();
+    if digits.len() != 13 || !digits.starts_with("756") {
+        return Err(Violation::BadFormat);
+    }
+
+    // Get the last digit
+    let check_number = match digits.chars().last().and_then(|c| c.to_digit(10)) {
+        Some(digit) => digit,
+        None => return Err(Violation::BadFormat),
     };
 
-    // check age
-    if age_int < age_config.min || age_int > age_config.max {
-        return Err(format!(
-            "Alter muss mindestens {} sein und nicht über {}.",
-            age_config.min, age_config.max
-        ));
+    // calculate for each digit the sum by multiplying every second digit by 3, starting from the right
+    let sum: u32 = digits[0..12]
+        .chars()
+        .rev()
+        .filter_map(|c| c.to_digit(10))
+        .enumerate()
+        .map(|(i, digit)| if i % 2 == 0 { digit * 3 } else { digit })
+        .sum();
+
+    // The check sum is the difference to the next ten
+    let check_sum = (10 - sum % 10) % 10;
+    if check_sum != check_number {
+        return Err(Violation::BadChecksum);
     }
+    Ok(AhvNumber::from_digits(&digits))
+}
 
-    let pensum_int = match pensum.parse::<u32>() {
-        Ok(pensum_int) => pensum_int,
-        Err(_) => return Err("Ungültiges Pensum.".to_string()),
+///# Validate a UID
+///
+/// Checks a Swiss company identification number like `CHE-123.456.788` with the mod 11 check digit.
+/// Accepts the number with dots, spaces, dashes or without separators, the prefix case insensitive,
+/// and returns it in the canonical notation `CHE-123.456.788`.
+/// Returns `bad_format` if the value isn't `CHE` followed by 9 digits and `bad_checksum` if the check digit is wrong.
+pub fn validate_uid(uid: &str) -> Result<Uid, Violation> {
+    let uid = uid.trim();
+    let number = match uid.get(0..3) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("CHE") => &uid[3..],
+        _ => return Err(Violation::BadFormat),
     };
+    // only digits and separators are allowed
+    if !number
+        .chars()
+        .all(|c| c.is_ascii_digit() || c.is_whitespace() || c == '.' || c == '-')
+    {
+        return Err(Violation::BadFormat);
+    }
+    let digits = number
+        .chars()
+        .filter_map(|c| c.to_digit(10))
+        .collect::<Vec<_>>();
+    if digits.len() != 9 {
+        return Err(Violation::BadFormat);
+    }
 
-    // check pensum
-    if pensum_int < pensum_config.min_value || pensum_int > pensum_config.max_value {
-        return Err(format!(
-            "Pensum muss mindestens {} sein und nicht über {}.",
-            pensum_config.min_value, pensum_config.max_value
-        ));
+    // weight the first 8 digits, the check digit completes the sum to a multiple of 11
+    let sum: u32 = digits[0..8]
+        .iter()
+        .zip([5, 4, 3, 2, 7, 6, 5, 4])
+        .map(|(digit, weight)| digit * weight)
+        .sum();
+    let check_sum = match 11 - sum % 11 {
+        11 => 0,
+        // no number has the check digit 10
+        10 => return Err(Violation::BadChecksum),
+        check_sum => check_sum,
+    };
+    if check_sum != digits[8] {
+        return Err(Violation::BadChecksum);
     }
+    let digits = digits
+        .iter()
+        .map(|digit| digit.to_string())
+        .collect::<String>();
+    Ok(Uid::from_digits(&digits))
+}
 
-    // check location
-    if location.len() < 2 || location.len() > 55 {
-        return Err(
-            "Ort muss mindestens 2 Buchstaben lang sein und nicht länger als 55 Zeichen."
-                .to_string(),
-        );
+///# Validate an IBAN
+///
+/// Checks a Swiss or Liechtenstein IBAN like `CH93 0076 2011 6238 5295 7` with the mod 97 check digits.
+/// Accepts the IBAN with or without spaces, case insensitive, and returns it in the electronic notation `CH9300762011623852957`.
+/// Returns `not_allowed` for IBANs of other countries, `bad_format` if the value has not 21 characters
+/// and `bad_checksum` if the check digits are wrong.
+pub fn validate_iban(iban: &str) -> Result<Iban, Violation> {
+    let compact = iban
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_ascii_uppercase();
+    if !compact.chars().all(|c| c.is_ascii_alphanumeric()) || compact.len() < 4 {
+        return Err(Violation::BadFormat);
+    }
+    if !compact.starts_with("CH") && !compact.starts_with("LI") {
+        return Err(Violation::NotAllowed);
+    }
+    // CH and LI: check digits, 5 digits bank clearing number and 12 characters account number
+    if compact.len() != 21 || !compact[2..9].chars().all(|c| c.is_ascii_digit()) {
+        return Err(Violation::BadFormat);
     }
 
-    // check occupation
-    if occupation.len() < 2 || occupation.len() > 55 {
-        return Err(
-            "Beruf muss mindestens 2 Buchstaben lang sein und nicht länger als 55 Zeichen."
-                .to_string(),
-        );
+    // move the country code and check digits to the end, letters count as 10 to 35
+    let remainder = compact[4..]
+        .chars()
+        .chain(compact[0..4].chars())
+        .filter_map(|c| c.to_digit(36))
+        .fold(0, |remainder, value| {
+            let factor = if value < 10 { 10 } else { 100 };
+            (remainder * factor + value) % 97
+        });
+    if remainder != 1 {
+        return Err(Violation::BadChecksum);
     }
+    Ok(Iban::from_compact(&compact))
+}
+
+/// Country code of Switzerland, Swiss numbers are always accepted
+const SWISS_COUNTRY_CODE: &str = "41";
 
-    validate_ahv(ahv_nr)?;
+///# Parse a phone number
+///
+/// Writes a phone number in the international E.164 notation `+41791234567`.
+/// Accepts the international notations `+41 79 123 45 67`, `+41 (0)79 123 45 67` and `0041 79 123 45 67`,
+/// numbers in the Swiss national notation like `079 123 45 67` get the Swiss country code.
+/// Spaces, dots, dashes, slashes and parentheses are allowed as separators.
+/// Returns `bad_format` if the value isn't a phone number or has more than the 15 digits of E.164.
+pub fn parse_phone(phone: &str) -> Result<PhoneNumber, Violation> {
+    // the trunk prefix in `+41 (0)79 ...` is not dialed from abroad
+    let phone = phone.trim().replace("(0)", "");
+    let (international, number) = match phone.strip_prefix('+') {
+        Some(number) => (true, number),
+        None => (false, phone.as_str()),
+    };
+    if !number.chars().all(|c| {
+        c.is_ascii_digit() || c.is_whitespace() || matches!(c, '.' | '-' | '/' | '(' | ')')
+    }) {
+        return Err(Violation::BadFormat);
+    }
+    let digits = number
+        .chars()
+        .filter(|c| c.is_ascii_digit())
+        .collect::<String>();
 
-    Ok(())
+    let digits = match (international, digits.strip_prefix('0')) {
+        (true, None) => digits,
+        // 00 is the international call prefix
+        (false, Some(national)) => match national.strip_prefix('0') {
+            Some(number) => number.to_string(),
+            None => format!("{}{}", SWISS_COUNTRY_CODE, national),
+        },
+        _ => return Err(Violation::BadFormat),
+    };
+    // `+41 079 ...` keeps the trunk prefix of the national notation
+    let digits = match digits.strip_prefix("410") {
+        Some(national) => format!("{}{}", SWISS_COUNTRY_CODE, national),
+        None => digits,
+    };
+    if digits.starts_with('0') || !(8..=15).contains(&digits.len()) {
+        return Err(Violation::BadFormat);
+    }
+    Ok(PhoneNumber::from_digits(&digits))
 }
 
-///# Validate optional fields for updates
+///# Validate a phone number
 ///
-/// Uses the valiadition rules and struct with optional fields to validate the user.
-pub fn validate_update(
-    config: Config,
-    first_name: &Option<String>,
-    last_name: &Option<String>,
-    age: &Option<String>,
-    pensum: &Option<String>,
-    location: &Option<String>,
-    occupation: &Option<String>,
-    ahv_nr: &Option<String>,
-) -> Result<(), String> {
-    // Extract the configs for the user
-    let name_config = config.validation_rules.person.name;
-    let age_config = config.validation_rules.person.age;
-    let pensum_config = config.validation_rules.person.pensum;
-
-    if let Some(first_name) = first_name {
-        if first_name.len() < name_config.min as usize
-            || first_name.len() > name_config.max as usize
+/// Parses the number with [`parse_phone`] and checks the settings of the `<phone>` element:
+/// Swiss numbers are always accepted and need 9 digits after the country code,
+/// numbers of other countries only if their country code is listed in `countryCodes`.
+/// Returns `not_allowed` for numbers of countries which are not listed.
+pub fn validate_phone(phone: &str, phone_config: &Phone) -> Result<PhoneNumber, Violation> {
+    let number = parse_phone(phone)?;
+    match number.digits().strip_prefix(SWISS_COUNTRY_CODE) {
+        Some(national) if national.len() == 9 => Ok(number),
+        Some(_) => Err(Violation::BadFormat),
+        None if phone_config.country_codes.iter().any(|code| {
+            let code = code.trim().trim_start_matches('+');
+            !code.is_empty() && number.digits().starts_with(code)
+        }) =>
         {
-            return Err(format!(
-                "Vorname muss mindestens {} Buchstaben lang sein und nicht länger als {} Zeichen.",
-                name_config.min, name_config.max
-            ));
+            Ok(number)
         }
+        None => Err(Violation::NotAllowed),
     }
+}
 
-    if let Some(last_name) = last_name {
-        if last_name.len() < name_config.min as usize || last_name.len() > name_config.max as usize
-        {
-            return Err(format!(
-                "Nachname muss mindestens {} Buchstaben lang sein und nicht länger als {} Zeichen.",
-                name_config.min, name_config.max
-            ));
-        }
+///# Validate an email address
+///
+/// Checks the syntax of `local@domain` and the settings of the `<email>` element:
+/// the maximum length, whether internationalized (non ASCII or punycode) addresses and plus addressing (`john+news@…`) are accepted,
+/// and, if any `<domain>` children are given, that the domain is one of them.
+/// Returns the checked address.
+pub fn validate_email(email: &str, email_config: &Email) -> Result<EmailAddress, Violation> {
+    if email.chars().count() > email_config.max_length as usize {
+        return Err(Violation::TooLong {
+            max: email_config.max_length,
+        });
     }
 
-    if let Some(age) = age {
-        let age_int: u32 = match age.parse::<u32>() {
-            Ok(age_int) => age_int,
-            Err(_) => return Err("Ungültiges Alter.".to_string()),
-        };
+    let (local, domain) = match email.rsplit_once('@') {
+        Some(parts) => parts,
+        None => return Err(Violation::BadFormat),
+    };
 
-        if age_int < age_config.min || age_int > age_config.max {
-            return Err(format!(
-                "Alter muss mindestens {} sein und nicht über {}.",
-                age_config.min, age_config.max
-            ));
-        }
+    // local part: dot separated atoms, no leading, trailing or double dots
+    let local_char =
+        |c: char| c.is_ascii_alphanumeric() || "!#$%&'*+/=?^_`{|}~-".contains(c) || !c.is_ascii();
+    if local.is_empty()
+        || local.len() > 64
+        || local
+            .split('.')
+            .any(|atom| atom.is_empty() || !atom.chars().all(local_char))
+    {
+        return Err(Violation::BadFormat);
     }
 
-    if let Some(pensum) = pensum {
-        let pensum_int = match pensum.parse::<u32>() {
-            Ok(pensum_int) => pensum_int,
-            Err(_) => return Err("Ungültiges Pensum.".to_string()),
-        };
+    // domain: at least two labels of letters, digits and inner hyphens, the top level domain without digits
+    // unless it is punycode like `xn--p1ai` (.рф)
+    let labels = domain.split('.').collect::<Vec<_>>();
+    let top_level = labels[labels.len() - 1];
+    if labels.len() < 2
+        || labels.iter().any(|label| {
+            label.is_empty()
+                || label.chars().count() > 63
+                || label.starts_with('-')
+                || label.ends_with('-')
+                || !label.chars().all(|c| c.is_alphanumeric() || c == '-')
+        })
+        || (!top_level.to_ascii_lowercase().starts_with("xn--")
+            && top_level.chars().any(|c| c.is_ascii_digit()))
+    {
+        return Err(Violation::BadFormat);
+    }
+
+    // punycode labels (`xn--`) are the ASCII form of internationalized domains
+    if !email_config.allow_idn
+        && (!email.is_ascii()
+            || labels
+                .iter()
+                .any(|label| label.to_ascii_lowercase().starts_with("xn--")))
+    {
+        return Err(Violation::IdnNotAllowed);
+    }
+
+    if !email_config.allow_plus && local.contains('+') {
+        return Err(Violation::PlusNotAllowed);
+    }
+
+    if !email_config.allowed_domains.is_empty()
+        && !email_config
+            .allowed_domains
+            .iter()
+            .any(|allowed| allowed.trim().eq_ignore_ascii_case(domain))
+    {
+        return Err(Violation::DomainNotAllowed {
+            domain: domain.to_lowercase(),
+        });
+    }
+
+    Ok(EmailAddress::new(email))
+}
 
-        if pensum_int < pensum_config.min_value || pensum_int > pensum_config.max_value {
-            return Err(format!(
-                "Pensum muss mindestens {} sein und nicht über {}.",
-                pensum_config.min_value, pensum_config.max_value
-            ));
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn user(name: &str, location: &str) -> Map<String, Value> {
+        let user = json!({
+            "first_name": name,
+            "last_name": name,
+            "age": "22",
+            "pensum": "77",
+            "location": location,
+            "occupation": "Software Engineer",
+            "ahv_nr": "756.1718.4457.72",
+        });
+        match user {
+            Value::Object(user) => user,
+            _ => unreachable!(),
         }
     }
 
-    if let Some(location) = location {
-        if location.len() < 2 || location.len() > 55 {
-            return Err(
-                "Ort muss mindestens 2 Buchstaben lang sein und nicht länger als 55 Zeichen."
-                    .to_string(),
-            );
+    fn config(unit: LengthUnit) -> Config {
+        let mut config = Config::default();
+        let person = &mut config.validation_rules.person;
+        person.name.min = 3;
+        person.name.max = 3;
+        person.name.unit = unit;
+        person.age.max = 120;
+        person.pensum.max_value = 100;
+        person.location.max = 6;
+        person.location.unit = unit;
+        config
+    }
+
+    fn failed_fields(result: Result<(), ValidationReport>) -> Vec<String> {
+        match result {
+            Ok(()) => Vec::new(),
+            Err(report) => report.errors.into_iter().map(|error| error.field).collect(),
         }
     }
 
-    if let Some(occupation) = occupation {
-        if occupation.len() < 2 || occupation.len() > 55 {
-            return Err(
-                "Beruf muss mindestens 2 Buchstaben lang sein und nicht länger als 55 Zeichen."
-                    .to_string(),
+    #[test]
+    fn umlauts_count_as_one_character() {
+        assert_eq!(text_length("Zürich", LengthUnit::Bytes), 7);
+        assert_eq!(text_length("Zürich", LengthUnit::Chars), 6);
+        assert_eq!(text_length("Zürich", LengthUnit::Graphemes), 6);
+    }
+
+    #[test]
+    fn combining_accents_count_as_one_grapheme() {
+        // "Zoë" with "e" followed by a combining diaeresis
+        let decomposed = "Zoe\u{308}";
+        assert_eq!(text_length(decomposed, LengthUnit::Bytes), 5);
+        assert_eq!(text_length(decomposed, LengthUnit::Chars), 4);
+        assert_eq!(text_length(decomposed, LengthUnit::Graphemes), 3);
+    }
+
+    #[test]
+    fn emoji_sequences_count_as_one_grapheme() {
+        assert_eq!(text_length("👍🏽", LengthUnit::Chars), 2);
+        assert_eq!(text_length("👍🏽", LengthUnit::Graphemes), 1);
+        assert_eq!(text_length("👨‍👩‍👧", LengthUnit::Chars), 5);
+        assert_eq!(text_length("👨‍👩‍👧", LengthUnit::Graphemes), 1);
+        assert_eq!(text_length("🇨🇭", LengthUnit::Chars), 2);
+        assert_eq!(text_length("🇨🇭", LengthUnit::Graphemes), 1);
+    }
+
+    #[test]
+    fn limits_use_the_configured_unit() {
+        let valid = user("Zoë", "Zürich");
+        assert_eq!(
+            failed_fields(validate_user(&config(LengthUnit::Chars), &valid)),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            failed_fields(validate_user(&config(LengthUnit::Bytes), &valid)),
+            vec!["first_name", "last_name", "location"]
+        );
+
+        let decomposed = user("Zoe\u{308}", "Zürich");
+        assert_eq!(
+            failed_fields(validate_user(&config(LengthUnit::Chars), &decomposed)),
+            vec!["first_name", "last_name"]
+        );
+        assert_eq!(
+            failed_fields(validate_user(&config(LengthUnit::Graphemes), &decomposed)),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn emoji_names_respect_the_limit() {
+        let emoji = user("Jo👍🏽", "Zürich");
+        assert_eq!(
+            failed_fields(validate_user(&config(LengthUnit::Chars), &emoji)),
+            vec!["first_name", "last_name"]
+        );
+        assert_eq!(
+            failed_fields(validate_user(&config(LengthUnit::Graphemes), &emoji)),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn ahv_numbers_are_canonicalized() {
+        for notation in [
+            "756.1718.4457.72",
+            "7561718445772",
+            "756 1718 4457 72",
+            "756-1718-4457-72",
+        ] {
+            assert_eq!(
+                validate_ahv(notation).map(|ahv_nr| ahv_nr.to_string()),
+                Ok("756.1718.4457.72".to_string())
             );
         }
+        assert_eq!(
+            validate_ahv("756.1718.4457.73"),
+            Err(Violation::BadChecksum)
+        );
+        assert_eq!(validate_ahv("123.1718.4457.72"), Err(Violation::BadFormat));
+        assert_eq!(validate_ahv("756.1718.4457.7"), Err(Violation::BadFormat));
     }
 
-    if let Some(ahv_nr) = ahv_nr {
-        validate_ahv(ahv_nr)?;
+    #[test]
+    fn masked_ahv_numbers_only_show_the_check_digits() {
+        assert_eq!(
+            validate_ahv("756 1718 4457 72").map(|ahv_nr| ahv_nr.masked()),
+            Ok("756.****.****.72".to_string())
+        );
     }
 
-    Ok(())
-}
+    #[test]
+    fn age_is_derived_from_the_date_of_birth() {
+        let date = |value: &str| parse_date(value).unwrap();
+        assert_eq!(date("17.05.1990"), date("1990-05-17"));
+        assert_eq!(parse_date("1990-02-30"), None);
 
-///# Validate the ahv number
-///
-/// This is the algorithm for validating the ahv number.
-pub fn validate_ahv(ahv_nr: &str) -> Result<(), String> {
-    // check ahv_nr with regex
-    let reg_exp = match Regex::new(r"^[7][5][6][.][\d]{4}[.][\d]{4}[.][\d]{2}$") {
-        Ok(regex) => regex,
-        Err(_) => return Err("Ungültige AHV Nummer.".to_string()),
-    };
+        assert_eq!(age_on(date("1990-05-17"), date("2024-05-16")), 33);
+        assert_eq!(age_on(date("1990-05-17"), date("2024-05-17")), 34);
+        // born on February 29
+        assert_eq!(age_on(date("2004-02-29"), date("2023-02-28")), 18);
+        assert_eq!(age_on(date("2004-02-29"), date("2023-03-01")), 19);
+        assert_eq!(age_on(date("2004-02-29"), date("2024-02-29")), 20);
+    }
 
-    if !reg_exp.is_match(&ahv_nr) {
-        return Err("Ungültige AHV Nummer.".to_string());
+    #[test]
+    fn uids_are_canonicalized() {
+        for notation in ["CHE-123.456.788", "CHE123456788", "che 123 456 788"] {
+            assert_eq!(
+                validate_uid(notation).map(|uid| uid.to_string()),
+                Ok("CHE-123.456.788".to_string())
+            );
+        }
+        assert!(validate_uid("CHE-116.281.710").is_ok());
+        assert_eq!(validate_uid("CHE-123.456.789"), Err(Violation::BadChecksum));
+        assert_eq!(validate_uid("DEU-123.456.788"), Err(Violation::BadFormat));
     }
 
-    // Get the last digit
-    let check_number = match ahv_nr.chars().last() {
-        Some(c) => match c.to_digit(10) {
-            Some(digit) => digit as i32,
-            None => return Err("Ungültige AHV Nummer.".to_string()),
-        },
-        None => return Err("Ungültige AHV Nummer.".to_string()),
-    };
+    #[test]
+    fn ibans_are_canonicalized() {
+        assert_eq!(
+            validate_iban("ch93 0076 2011 6238 5295 7").map(|iban| iban.to_string()),
+            Ok("CH9300762011623852957".to_string())
+        );
+        assert!(validate_iban("LI21 0881 0000 2324 013A A").is_ok());
+        assert_eq!(
+            validate_iban("CH94 0076 2011 6238 5295 7"),
+            Err(Violation::BadChecksum)
+        );
+        assert_eq!(
+            validate_iban("DE89 3704 0044 0532 0130 00"),
+            Err(Violation::NotAllowed)
+        );
+        assert_eq!(validate_iban("CH93 0076 2011"), Err(Violation::BadFormat));
+    }
 
-    // Remove the last digit
-    let ahv_nr = &ahv_nr[0..ahv_nr.len() - 1];
+    #[test]
+    fn phone_numbers_are_written_in_e164() {
+        let phone = Phone::default();
+        for notation in [
+            "079 123 45 67",
+            "+41 79 123 45 67",
+            "+41 (0)79 123 45 67",
+            "0041 79 123 45 67",
+            "079/123.45.67",
+        ] {
+            assert_eq!(
+                validate_phone(notation, &phone).map(|number| number.to_string()),
+                Ok("+41791234567".to_string())
+            );
+        }
+        assert_eq!(
+            validate_phone("079 123 45", &phone),
+            Err(Violation::BadFormat)
+        );
+        assert_eq!(
+            validate_phone("+49 30 1234567", &phone),
+            Err(Violation::NotAllowed)
+        );
+        let phone = Phone {
+            country_codes: vec!["+49".to_string()],
+            ..Phone::default()
+        };
+        assert_eq!(
+            validate_phone("0049 30 1234567", &phone).map(|number| number.to_string()),
+            Ok("+49301234567".to_string())
+        );
+    }
 
-    // Remove the dots and reverse the string
-    let normalized = ahv_nr.replace(".", "").chars().rev().collect::<String>();
+    #[test]
+    fn emails_are_checked_against_the_settings() {
+        let settings = Email::default();
+        assert!(validate_email("john.doe@example.com", &settings).is_ok());
+        assert_eq!(
+            validate_email("john..doe@example.com", &settings),
+            Err(Violation::BadFormat)
+        );
+        assert_eq!(
+            validate_email("john@localhost", &settings),
+            Err(Violation::BadFormat)
+        );
 
-    // calculate for each digit the sum by multiplying every second digit by 3
-    let mut sum = 0;
-    for (i, digit) in normalized.chars().enumerate() {
-        let digit = match digit.to_digit(10) {
-            Some(digit) => digit as i32,
-            None => return Err("Invalid digit".into()), // or handle the error as you see fit
+        let settings = Email {
+            max_length: 20,
+            ..Email::default()
         };
-
-        let add = if i % 2 == 0 { digit * 3 } else { digit };
-        sum += add;
+        assert_eq!(
+            validate_email("john.doe@example.com", &settings).map(|email| email.to_string()),
+            Ok("john.doe@example.com".to_string())
+        );
+        assert_eq!(
+            validate_email("johnny.doe@example.com", &settings),
+            Err(Violation::TooLong { max: 20 })
+        );
     }
 
-    // Calculate the next ten by rounding up the sum divided by 10 and multiply by 10
-    let next_ten = ((sum as f32 / 10.0).ceil() * 10.0) as i32;
+    #[test]
+    fn idn_and_plus_addresses_can_be_rejected() {
+        let settings = Email {
+            allow_idn: false,
+            allow_plus: false,
+            ..Email::default()
+        };
+        assert!(validate_email("jöhn@müller.ch", &Email::default()).is_ok());
+        assert_eq!(
+            validate_email("jöhn@müller.ch", &settings),
+            Err(Violation::IdnNotAllowed)
+        );
+        assert_eq!(
+            validate_email("john@xn--mller-kva.ch", &settings),
+            Err(Violation::IdnNotAllowed)
+        );
+        // punycode top level domains contain digits
+        assert!(validate_email("a@пример.рф", &Email::default()).is_ok());
+        assert!(validate_email("a@xn--e1afmkfd.xn--p1ai", &Email::default()).is_ok());
+        assert_eq!(
+            validate_email("a@xn--e1afmkfd.xn--p1ai", &settings),
+            Err(Violation::IdnNotAllowed)
+        );
+        assert_eq!(
+            validate_email("a@example.c0m", &Email::default()),
+            Err(Violation::BadFormat)
+        );
+        assert!(validate_email("john+news@example.com", &Email::default()).is_ok());
+        assert_eq!(
+            validate_email("john+news@example.com", &settings),
+            Err(Violation::PlusNotAllowed)
+        );
+    }
 
-    // Calculate the check sum by subtracting the sum from the next ten
-    let check_sum = next_ten - sum;
-    if check_sum != check_number {
-        return Err("Ungültige AHV Nummer.".to_string());
+    #[test]
+    fn only_allowed_domains_are_accepted() {
+        let settings = Email {
+            allowed_domains: vec!["example.com".to_string()],
+            ..Email::default()
+        };
+        assert!(validate_email("john@Example.com", &settings).is_ok());
+        assert_eq!(
+            validate_email("john@Other.ch", &settings),
+            Err(Violation::DomainNotAllowed {
+                domain: "other.ch".to_string()
+            })
+        );
     }
-    Ok(())
 }
-
-// TESTS
@@ -0,0 +1,403 @@
+//! This module contains the message catalog used to turn validation errors into localized text.
+//!
+//! Messages are keyed by the rule `code` of a [`Violation`](crate::models::validation_models::Violation) and may use
+//! the placeholders `{field}` and the names of the rule parameters, e.g. `{min}` and `{max}`.
+use crate::models::validation_models::ValidationError;
+
+/// The languages the catalog has messages for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Language {
+    #[default]
+    De,
+    Fr,
+    It,
+    En,
+}
+
+/// The messages and field labels of a single language.
+struct Catalog {
+    messages: &'static [(&'static str, &'static str)],
+    labels: &'static [(&'static str, &'static str)],
+}
+
+const DE: Catalog = Catalog {
+    messages: &[
+        (
+            "too_short",
+            "{field} muss mindestens {min} Zeichen lang sein.",
+        ),
+        (
+            "too_long",
+            "{field} darf nicht länger als {max} Zeichen sein.",
+        ),
+        (
+            "out_of_range",
+            "{field} muss mindestens {min} sein und nicht über {max}.",
+        ),
+        ("not_a_number", "{field} ist keine gültige Zahl."),
+        ("bad_format", "{field} hat ein ungültiges Format."),
+        ("bad_checksum", "{field} hat eine ungültige Prüfziffer."),
+        ("required", "{field} ist ein Pflichtfeld."),
+        (
+            "domain_not_allowed",
+            "{field}: Die Domain {domain} ist nicht erlaubt.",
+        ),
+        (
+            "idn_not_allowed",
+            "{field} darf keine Sonderzeichen enthalten.",
+        ),
+        ("plus_not_allowed", "{field} darf kein \"+\" enthalten."),
+        ("pattern_mismatch", "{field} enthält ungültige Zeichen."),
+        ("not_allowed", "{field} ist kein zulässiger Wert."),
+        ("unknown_field", "{field} ist kein bekanntes Feld."),
+        ("rule_violated", "{field} verletzt die Regel \"{rule}\"."),
+        (
+            "unknown_postcode",
+            "{field}: Die Postleitzahl ist unbekannt.",
+        ),
+        (
+            "postcode_mismatch",
+            "{field}: Postleitzahl und Ort passen nicht zusammen. Meinten Sie {suggestion}?",
+        ),
+        ("unknown_occupation", "{field} ist kein bekannter Beruf."),
+        ("too_early", "{field} darf nicht vor dem {min} liegen."),
+        ("too_late", "{field} darf nicht nach dem {max} liegen."),
+        (
+            "age_mismatch",
+            "{field} passt nicht zum Geburtsdatum, erwartet wird {age}.",
+        ),
+    ],
+    labels: &[
+        ("first_name", "Vorname"),
+        ("last_name", "Nachname"),
+        ("age", "Alter"),
+        ("pensum", "Pensum"),
+        ("location", "Ort"),
+        ("occupation", "Beruf"),
+        ("ahv_nr", "AHV Nummer"),
+        ("date_of_birth", "Geburtsdatum"),
+        ("email", "E-Mail"),
+        ("phone", "Telefonnummer"),
+        ("row", "Zeile"),
+    ],
+};
+
+const FR: Catalog = Catalog {
+    messages: &[
+        (
+            "too_short",
+            "{field} doit comporter au moins {min} caractères.",
+        ),
+        ("too_long", "{field} ne doit pas dépasser {max} caractères."),
+        (
+            "out_of_range",
+            "{field} doit être compris entre {min} et {max}.",
+        ),
+        ("not_a_number", "{field} n'est pas un nombre valide."),
+        ("bad_format", "{field} a un format invalide."),
+        ("bad_checksum", "{field} a un chiffre de contrôle invalide."),
+        ("required", "{field} est obligatoire."),
+        (
+            "domain_not_allowed",
+            "{field} : le domaine {domain} n'est pas autorisé.",
+        ),
+        (
+            "idn_not_allowed",
+            "{field} ne doit pas contenir de caractères spéciaux.",
+        ),
+        ("plus_not_allowed", "{field} ne doit pas contenir de « + »."),
+        (
+            "pattern_mismatch",
+            "{field} contient des caractères non valides.",
+        ),
+        ("not_allowed", "{field} n'est pas une valeur autorisée."),
+        ("unknown_field", "{field} n'est pas un champ connu."),
+        (
+            "rule_violated",
+            "{field} ne respecte pas la règle « {rule} ».",
+        ),
+        ("unknown_postcode", "{field} : le NPA est inconnu."),
+        (
+            "postcode_mismatch",
+            "{field} : le NPA et la localité ne correspondent pas. Vouliez-vous dire {suggestion} ?",
+        ),
+        ("unknown_occupation", "{field} n'est pas une profession connue."),
+        ("too_early", "{field} ne doit pas être antérieure au {min}."),
+        ("too_late", "{field} ne doit pas être postérieure au {max}."),
+        (
+            "age_mismatch",
+            "{field} ne correspond pas à la date de naissance, {age} est attendu.",
+        ),
+    ],
+    labels: &[
+        ("first_name", "Prénom"),
+        ("last_name", "Nom"),
+        ("age", "Âge"),
+        ("pensum", "Taux d'occupation"),
+        ("location", "Lieu"),
+        ("occupation", "Profession"),
+        ("ahv_nr", "Numéro AVS"),
+        ("date_of_birth", "Date de naissance"),
+        ("email", "E-mail"),
+        ("phone", "Numéro de téléphone"),
+        ("row", "Ligne"),
+    ],
+};
+
+const IT: Catalog = Catalog {
+    messages: &[
+        (
+            "too_short",
+            "{field} deve contenere almeno {min} caratteri.",
+        ),
+        ("too_long", "{field} non deve superare {max} caratteri."),
+        (
+            "out_of_range",
+            "{field} deve essere compreso tra {min} e {max}.",
+        ),
+        ("not_a_number", "{field} non è un numero valido."),
+        ("bad_format", "{field} ha un formato non valido."),
+        (
+            "bad_checksum",
+            "{field} ha una cifra di controllo non valida.",
+        ),
+        ("required", "{field} è obbligatorio."),
+        (
+            "domain_not_allowed",
+            "{field}: il dominio {domain} non è consentito.",
+        ),
+        (
+            "idn_not_allowed",
+            "{field} non deve contenere caratteri speciali.",
+        ),
+        ("plus_not_allowed", "{field} non deve contenere \"+\"."),
+        ("pattern_mismatch", "{field} contiene caratteri non validi."),
+        ("not_allowed", "{field} non è un valore consentito."),
+        ("unknown_field", "{field} non è un campo conosciuto."),
+        (
+            "rule_violated",
+            "{field} non rispetta la regola \"{rule}\".",
+        ),
+        ("unknown_postcode", "{field}: il NPA è sconosciuto."),
+        (
+            "postcode_mismatch",
+            "{field}: NPA e località non corrispondono. Intendeva {suggestion}?",
+        ),
+        (
+            "unknown_occupation",
+            "{field} non è una professione conosciuta.",
+        ),
+        ("too_early", "{field} non deve essere anteriore al {min}."),
+        ("too_late", "{field} non deve essere successiva al {max}."),
+        (
+            "age_mismatch",
+            "{field} non corrisponde alla data di nascita, è previsto {age}.",
+        ),
+    ],
+    labels: &[
+        ("first_name", "Nome"),
+        ("last_name", "Cognome"),
+        ("age", "Età"),
+        ("pensum", "Grado di occupazione"),
+        ("location", "Luogo"),
+        ("occupation", "Professione"),
+        ("ahv_nr", "Numero AVS"),
+        ("date_of_birth", "Data di nascita"),
+        ("email", "E-mail"),
+        ("phone", "Numero di telefono"),
+        ("row", "Riga"),
+    ],
+};
+
+const EN: Catalog = Catalog {
+    messages: &[
+        (
+            "too_short",
+            "{field} must be at least {min} characters long.",
+        ),
+        (
+            "too_long",
+            "{field} must not be longer than {max} characters.",
+        ),
+        ("out_of_range", "{field} must be between {min} and {max}."),
+        ("not_a_number", "{field} is not a valid number."),
+        ("bad_format", "{field} has an invalid format."),
+        ("bad_checksum", "{field} has an invalid check digit."),
+        ("required", "{field} is required."),
+        (
+            "domain_not_allowed",
+            "{field}: the domain {domain} is not allowed.",
+        ),
+        (
+            "idn_not_allowed",
+            "{field} must not contain special characters.",
+        ),
+        ("plus_not_allowed", "{field} must not contain \"+\"."),
+        ("pattern_mismatch", "{field} contains invalid characters."),
+        ("not_allowed", "{field} is not an allowed value."),
+        ("unknown_field", "{field} is not a known field."),
+        ("rule_violated", "{field} violates the rule \"{rule}\"."),
+        ("unknown_postcode", "{field}: the postcode is unknown."),
+        (
+            "postcode_mismatch",
+            "{field}: postcode and locality don't match. Did you mean {suggestion}?",
+        ),
+        ("unknown_occupation", "{field} is not a known occupation."),
+        ("too_early", "{field} must not be before {min}."),
+        ("too_late", "{field} must not be after {max}."),
+        (
+            "age_mismatch",
+            "{field} doesn't match the date of birth, expected {age}.",
+        ),
+    ],
+    labels: &[
+        ("first_name", "First name"),
+        ("last_name", "Last name"),
+        ("age", "Age"),
+        ("pensum", "Pensum"),
+        ("location", "Location"),
+        ("occupation", "Occupation"),
+        ("ahv_nr", "AHV number"),
+        ("date_of_birth", "Date of birth"),
+        ("email", "Email"),
+        ("phone", "Phone number"),
+        ("row", "Row"),
+    ],
+};
+
+impl Language {
+    ///# Parses a language code
+    ///
+    /// Accepts plain codes and language tags with a region like `de-CH`, case insensitive.
+    pub fn from_code(code: &str) -> Option<Language> {
+        let primary = code.trim().split(['-', '_']).next()?.to_ascii_lowercase();
+        match primary.as_str() {
+            "de" => Some(Language::De),
+            "fr" => Some(Language::Fr),
+            "it" => Some(Language::It),
+            "en" => Some(Language::En),
+            _ => None,
+        }
+    }
+
+    ///# Picks a language from an `Accept-Language` header
+    ///
+    /// Returns the supported language with the highest quality value, e.g. `fr` for `en-US;q=0.5, fr-CH`.
+    pub fn from_accept_language(header: &str) -> Option<Language> {
+        let mut candidates = header
+            .split(',')
+            .filter_map(|entry| {
+                let mut parts = entry.split(';');
+                let language = Language::from_code(parts.next()?)?;
+                let quality = parts
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+                Some((language, quality))
+            })
+            .filter(|(_, quality)| *quality > 0.0)
+            .collect::<Vec<_>>();
+        // stable sort keeps the header order for equal quality values
+        candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+        candidates.first().map(|(language, _)| *language)
+    }
+
+    fn catalog(self) -> &'static Catalog {
+        match self {
+            Language::De => &DE,
+            Language::Fr => &FR,
+            Language::It => &IT,
+            Language::En => &EN,
+        }
+    }
+}
+
+///# Renders a validation error in the given language
+///
+/// Unknown fields are shown with their key, unknown rule codes fall back to the code itself.
+pub fn message(error: &ValidationError, language: Language) -> String {
+    let catalog = language.catalog();
+    let label = lookup(catalog.labels, &error.field).unwrap_or(error.field.as_str());
+
+    let violation = serde_json::to_value(&error.violation).unwrap_or_default();
+    let code = violation["code"].as_str().unwrap_or_default();
+    let mut text = lookup(catalog.messages, code)
+        .unwrap_or(code)
+        .replace("{field}", label);
+    if let Some(params) = violation["params"].as_object() {
+        for (name, value) in params {
+            let value = match value.as_str() {
+                Some(value) => value.to_string(),
+                None => value.to_string(),
+            };
+            text = text.replace(&format!("{{{}}}", name), &value);
+        }
+    }
+    text
+}
+
+fn lookup(entries: &[(&'static str, &'static str)], key: &str) -> Option<&'static str> {
+    entries
+        .iter()
+        .find(|(entry_key, _)| *entry_key == key)
+        .map(|(_, value)| *value)
+}
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::validation_models::Violation;
+
+    fn error(field: &str, violation: Violation) -> ValidationError {
+        ValidationError {
+            field: field.to_string(),
+            violation,
+        }
+    }
+
+    #[test]
+    fn the_language_with_the_highest_quality_is_picked() {
+        assert_eq!(
+            Language::from_accept_language("en-US;q=0.5, fr-CH"),
+            Some(Language::Fr)
+        );
+        assert_eq!(
+            Language::from_accept_language("es, it;q=0.8, de;q=0.8"),
+            Some(Language::It)
+        );
+        assert_eq!(
+            Language::from_accept_language("DE_ch;q=0.9, en;q=0"),
+            Some(Language::De)
+        );
+        assert_eq!(Language::from_accept_language("es, pt;q=0.9"), None);
+        assert_eq!(Language::from_accept_language("fr;q=abc"), None);
+    }
+
+    #[test]
+    fn messages_fill_in_the_label_and_the_params() {
+        let out_of_range = error("age", Violation::OutOfRange { min: 12, max: 120 });
+        assert_eq!(
+            message(&out_of_range, Language::En),
+            "Age must be between 12 and 120."
+        );
+        assert_eq!(
+            message(&out_of_range, Language::Fr),
+            "Âge doit être compris entre 12 et 120."
+        );
+        assert_eq!(
+            message(&error("nickname", Violation::Required), Language::De),
+            "nickname ist ein Pflichtfeld."
+        );
+        let mismatch = error(
+            "location",
+            Violation::PostcodeMismatch {
+                suggestion: "8001 Zürich".to_string(),
+            },
+        );
+        assert_eq!(
+            message(&mismatch, Language::It),
+            "Luogo: NPA e località non corrispondono. Intendeva 8001 Zürich?"
+        );
+    }
+}
@@ -1,2 +1,9 @@
+pub mod batch;
+pub mod csv_import;
 pub mod lib;
+pub mod messages;
+pub mod normalizer;
+pub mod occupations;
+pub mod postcodes;
+pub mod rule_engine;
 pub mod xml_live_reader;
\ No newline at end of file
@@ -0,0 +1,210 @@
+//! This module contains the normalization stage. It cleans up the values of a document before they are validated,
+//! the normalized values are the ones which get stored.
+use crate::{
+    library::lib::{
+        age_on, parse_date, today, validate_ahv, validate_iban, validate_phone, validate_uid,
+    },
+    models::xml_models::{Config, FieldType, NormalizeStep},
+};
+use serde_json::{Map, Value};
+use unicode_normalization::UnicodeNormalization;
+
+///# Normalizes a document
+///
+/// Applies the `normalize` steps declared for each field to its text value, other values are left untouched.
+/// Valid Swiss locations are written as object with `plz`, `locality` and `canton`,
+/// valid occupations of the code list as object with `code` and `label`.
+/// Dates, UIDs, IBANs and phone numbers are written in their canonical notation.
+pub fn normalize(config: &Config, document: &mut Map<String, Value>) {
+    for rule in config.validation_rules.person.field_rules() {
+        if let Some(value) = document.get_mut(&rule.name) {
+            let structured = match rule.type_ {
+                FieldType::SwissLocation => config
+                    .postcodes
+                    .validate(value)
+                    .ok()
+                    .and_then(|location| serde_json::to_value(location).ok()),
+                FieldType::Occupation => config
+                    .occupations
+                    .validate(value)
+                    .ok()
+                    .and_then(|occupation| serde_json::to_value(occupation).ok()),
+                FieldType::Date => value
+                    .as_str()
+                    .and_then(parse_date)
+                    .map(|date| Value::from(date.to_string())),
+                FieldType::Uid => value
+                    .as_str()
+                    .and_then(|uid| validate_uid(uid).ok())
+                    .map(|uid| Value::from(uid.to_string())),
+                FieldType::Iban => value
+                    .as_str()
+                    .and_then(|iban| validate_iban(iban).ok())
+                    .map(|iban| Value::from(iban.to_string())),
+                FieldType::Phone => value
+                    .as_str()
+                    .and_then(|phone| {
+                        validate_phone(phone, &config.validation_rules.person.phone).ok()
+                    })
+                    .map(|phone| Value::from(phone.to_string())),
+                _ => None,
+            };
+            if let Some(structured) = structured {
+                *value = structured;
+                continue;
+            }
+        }
+        if rule.normalize.is_empty() {
+            continue;
+        }
+        if let Some(Value::String(text)) = document.get_mut(&rule.name) {
+            *text = normalize_text(text, &rule.normalize);
+        }
+    }
+}
+
+///# Fills in the defaults of omitted fields
+///
+/// Every field with a `default` attribute which is missing or `null` in the document gets its default,
+/// as a number for integer fields. Returns the names of the filled in fields. Only used on create.
+pub fn apply_defaults(config: &Config, document: &mut Map<String, Value>) -> Vec<String> {
+    let mut applied = Vec::new();
+    for rule in config.validation_rules.person.field_rules() {
+        let default = match &rule.default {
+            Some(default) => default,
+            None => continue,
+        };
+        if !document.get(&rule.name).is_none_or(Value::is_null) {
+            continue;
+        }
+        let value = match (rule.type_, default.trim().parse::<i64>()) {
+            (FieldType::Integer, Ok(number)) => Value::from(number),
+            _ => Value::from(default.as_str()),
+        };
+        document.insert(rule.name.clone(), value);
+        applied.push(rule.name);
+    }
+    applied
+}
+
+///# Derives the age from the date of birth
+///
+/// If the document has a valid date in the field of `<age derivedFrom>` but no `age`, the age is filled in,
+/// so the `<age>` limits apply to the derived age. A given age is checked by the rule engine.
+pub fn derive_age(config: &Config, document: &mut Map<String, Value>) {
+    if document.contains_key("age") {
+        return;
+    }
+    if let Some(age) = derived_age(config, document) {
+        document.insert("age".to_string(), Value::from(age));
+    }
+}
+
+///# Calculates the age from the date of birth
+///
+/// Returns `None` if the config derives no age or the document has no valid date in the field of `<age derivedFrom>`.
+pub fn derived_age(config: &Config, document: &Map<String, Value>) -> Option<u32> {
+    let derived_from = config.validation_rules.person.age.derived_from.as_ref()?;
+    let date_of_birth = document
+        .get(derived_from)
+        .and_then(Value::as_str)
+        .and_then(parse_date)?;
+    Some(age_on(date_of_birth, today()))
+}
+
+///# Normalizes a single text
+///
+/// The steps are applied in the given order.
+pub fn normalize_text(value: &str, steps: &[NormalizeStep]) -> String {
+    steps
+        .iter()
+        .fold(value.to_string(), |value, step| match step {
+            NormalizeStep::Trim => value.trim().to_string(),
+            NormalizeStep::Collapse => value.split_whitespace().collect::<Vec<_>>().join(" "),
+            NormalizeStep::Nfc => value.nfc().collect(),
+            NormalizeStep::Title => title_case(&value),
+            NormalizeStep::Lowercase => value.to_lowercase(),
+            NormalizeStep::Ahv => match validate_ahv(&value) {
+                Ok(ahv_nr) => ahv_nr.to_string(),
+                Err(_) => value,
+            },
+        })
+}
+
+/// Upper cases the first letter of every word and lower cases the rest.
+/// Words are separated by whitespace, hyphens and apostrophes, so "o'NEILL-smith" becomes "O'Neill-Smith".
+fn title_case(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut word_start = true;
+    for c in value.chars() {
+        if word_start {
+            result.extend(c.to_uppercase());
+        } else {
+            result.extend(c.to_lowercase());
+        }
+        word_start = c.is_whitespace() || c == '-' || c == '\'' || c == '’';
+    }
+    result
+}
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_are_applied_in_order() {
+        use NormalizeStep::*;
+        assert_eq!(
+            normalize_text("  jean   luc ", &[Trim, Collapse]),
+            "jean luc"
+        );
+        // "e" followed by a combining acute accent
+        assert_eq!(normalize_text("Rene\u{301}", &[Nfc]), "René");
+        assert_eq!(
+            normalize_text(" JOHN@Example.COM ", &[Trim, Lowercase]),
+            "john@example.com"
+        );
+        assert_eq!(
+            normalize_text("756 1718 4457 72", &[Ahv]),
+            "756.1718.4457.72"
+        );
+        assert_eq!(normalize_text("756 1718", &[Ahv]), "756 1718");
+        assert_eq!(normalize_text("  ", &[Trim, Title]), "");
+    }
+
+    #[test]
+    fn title_case_starts_every_word_upper_case() {
+        assert_eq!(title_case("o'NEILL-smith"), "O'Neill-Smith");
+        assert_eq!(title_case("jean-luc PICARD"), "Jean-Luc Picard");
+        assert_eq!(title_case("d’alembert"), "D’Alembert");
+        assert_eq!(title_case("élodie"), "Élodie");
+    }
+
+    #[test]
+    fn omitted_fields_get_their_default() {
+        let config = Config::from_xml(
+            r#"<config><validationRules><person>
+                <pensum type="integer" minValue="10" maxValue="100" default="100" />
+                <field name="status" type="string" default="active" />
+            </person></validationRules></config>"#,
+        )
+        .unwrap();
+
+        let mut document = Map::new();
+        document.insert("status".to_string(), Value::Null);
+        let applied = apply_defaults(&config, &mut document);
+        assert_eq!(applied, vec!["pensum".to_string(), "status".to_string()]);
+        assert_eq!(document["pensum"], Value::from(100));
+        assert_eq!(document["status"], Value::from("active"));
+
+        let mut document = Map::new();
+        document.insert("pensum".to_string(), Value::from(50));
+        assert_eq!(
+            apply_defaults(&config, &mut document),
+            vec!["status".to_string()]
+        );
+        assert_eq!(document["pensum"], Value::from(50));
+    }
+}
@@ -0,0 +1,172 @@
+//! This module contains the occupation code list used to validate occupations against a controlled vocabulary.
+//!
+//! The list is a semicolon separated file with the columns `code;label` and a header row, e.g. CH-ISCO-19 codes.
+//! A code may appear on several lines to add synonyms, the first label of a code is the one which gets stored.
+//! It is referenced with `<occupation codes="data/occupations.csv" />` and loaded together with the config.
+use crate::{
+    library::lib::comparable,
+    models::{domain_models::OccupationCode, validation_models::Violation},
+};
+use serde_json::Value;
+use std::{fmt, fs, io, sync::Arc};
+
+/// # Summary
+/// The entries of the code list in file order, synonyms included, shared like [`Postcodes`](crate::library::postcodes::Postcodes).
+#[derive(Clone, Default)]
+pub struct Occupations(Arc<Vec<OccupationCode>>);
+
+impl Occupations {
+    ///# Reads a code list file
+    ///
+    /// Empty lines are skipped, a line without two columns is reported as `InvalidData`.
+    pub fn load(path: &str) -> Result<Occupations, io::Error> {
+        let content = fs::read_to_string(path)?;
+        let mut entries = Vec::new();
+        for (number, line) in content.lines().enumerate().skip(1) {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match line.split(';').map(str::trim).collect::<Vec<_>>()[..] {
+                [code, label] => entries.push(OccupationCode {
+                    code: code.to_string(),
+                    label: label.to_string(),
+                }),
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("line {} has not 2 columns", number + 1),
+                    ))
+                }
+            }
+        }
+        Ok(Occupations(Arc::new(entries)))
+    }
+
+    ///# Looks up an occupation by code or label
+    ///
+    /// Labels are compared case and accent insensitive. Returns the code with its first label.
+    pub fn lookup(&self, value: &str) -> Option<OccupationCode> {
+        let value = value.trim();
+        let key = comparable(value);
+        let code = self
+            .0
+            .iter()
+            .find(|entry| entry.code == value || comparable(&entry.label) == key)?
+            .code
+            .as_str();
+        self.0.iter().find(|entry| entry.code == code).cloned()
+    }
+
+    ///# Validates an occupation
+    ///
+    /// Accepts a code or label as text, or an object with `code` and an optional `label`.
+    /// Returns `unknown_occupation` if the occupation isn't in the list.
+    pub fn validate(&self, value: &Value) -> Result<OccupationCode, Violation> {
+        let value = match value {
+            Value::String(text) => text.as_str(),
+            Value::Object(occupation) => occupation
+                .get("code")
+                .and_then(Value::as_str)
+                .ok_or(Violation::BadFormat)?,
+            _ => return Err(Violation::BadFormat),
+        };
+        self.lookup(value).ok_or(Violation::UnknownOccupation)
+    }
+
+    ///# Searches occupations for autocompletion
+    ///
+    /// Returns up to `limit` entries whose code starts with `prefix`, or with a word of the label starting with it.
+    /// Synonyms are listed with their own label.
+    pub fn search(&self, prefix: &str, limit: usize) -> Vec<OccupationCode> {
+        let prefix = comparable(prefix);
+        if prefix.is_empty() {
+            return Vec::new();
+        }
+        self.0
+            .iter()
+            .filter(|entry| {
+                let label = comparable(&entry.label);
+                entry.code.starts_with(&prefix)
+                    || label.starts_with(&prefix)
+                    || label
+                        .split([' ', '/', '-'])
+                        .any(|word| word.starts_with(&prefix))
+            })
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+impl fmt::Debug for Occupations {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Occupations({} entries)", self.0.len())
+    }
+}
+
+impl PartialEq for Occupations {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0) || self.0 == other.0
+    }
+}
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn occupations() -> Occupations {
+        let entry = |code: &str, label: &str| OccupationCode {
+            code: code.to_string(),
+            label: label.to_string(),
+        };
+        Occupations(Arc::new(vec![
+            entry("2512", "Softwareentwickler/in"),
+            entry("2512", "Programmierer/in"),
+            entry("2221", "Pflegefachfrau/Pflegefachmann"),
+            entry("5120", "Koch/Köchin"),
+        ]))
+    }
+
+    #[test]
+    fn synonyms_are_stored_with_the_first_label() {
+        let software = occupations().lookup("2512");
+        assert_eq!(
+            software.as_ref().map(|entry| entry.label.as_str()),
+            Some("Softwareentwickler/in")
+        );
+        assert_eq!(occupations().lookup(" PROGRAMMIERER/IN "), software);
+        assert_eq!(
+            occupations().lookup("koch/kochin").map(|entry| entry.code),
+            Some("5120".to_string())
+        );
+        assert_eq!(occupations().lookup("Bäcker/in"), None);
+        assert_eq!(
+            occupations().validate(&serde_json::json!({"code": "9999"})),
+            Err(Violation::UnknownOccupation)
+        );
+    }
+
+    #[test]
+    fn search_matches_codes_and_words_of_labels() {
+        let labels = |prefix: &str, limit: usize| {
+            occupations()
+                .search(prefix, limit)
+                .into_iter()
+                .map(|entry| entry.label)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            labels("25", 10),
+            vec!["Softwareentwickler/in", "Programmierer/in"]
+        );
+        assert_eq!(labels("25", 1), vec!["Softwareentwickler/in"]);
+        assert_eq!(
+            labels("pflegefachm", 10),
+            vec!["Pflegefachfrau/Pflegefachmann"]
+        );
+        assert_eq!(labels("kochin", 10), vec!["Koch/Köchin"]);
+        assert!(labels(" ", 10).is_empty());
+    }
+}
@@ -0,0 +1,214 @@
+//! This module contains the Swiss postcode dataset used to validate structured locations.
+//!
+//! The dataset is a semicolon separated file with the columns `plz;locality;canton` and a header row.
+//! It is referenced with `<location postcodes="data/postcodes.csv" />` and loaded together with the config,
+//! changes of the file are only picked up when the config is reloaded.
+use crate::{
+    library::lib::comparable,
+    models::{domain_models::SwissLocation, validation_models::Violation},
+};
+use serde_json::Value;
+use std::{collections::HashMap, fmt, fs, io, sync::Arc};
+
+/// # Summary
+/// The localities of the dataset, keyed by their postcode.
+///
+/// Cloning is cheap, the entries are shared between all copies of the config.
+#[derive(Clone, Default)]
+pub struct Postcodes(Arc<HashMap<String, Vec<SwissLocation>>>);
+
+impl Postcodes {
+    ///# Reads a dataset file
+    ///
+    /// Empty lines are skipped, a line without three columns is reported as `InvalidData`.
+    pub fn load(path: &str) -> Result<Postcodes, io::Error> {
+        let content = fs::read_to_string(path)?;
+        let mut entries: HashMap<String, Vec<SwissLocation>> = HashMap::new();
+        for (number, line) in content.lines().enumerate().skip(1) {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let columns = line.split(';').map(str::trim).collect::<Vec<_>>();
+            let (plz, locality, canton) = match columns[..] {
+                [plz, locality, canton] => (plz, locality, canton),
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("line {} has not 3 columns", number + 1),
+                    ))
+                }
+            };
+            entries
+                .entry(plz.to_string())
+                .or_default()
+                .push(SwissLocation {
+                    plz: plz.to_string(),
+                    locality: locality.to_string(),
+                    canton: canton.to_string(),
+                });
+        }
+        Ok(Postcodes(Arc::new(entries)))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    ///# Validates a location
+    ///
+    /// Accepts a text like `8001 Zürich` or an object with `plz`, `locality` and an optional `canton`.
+    /// The locality is compared case and accent insensitive, the returned location is spelled like in the dataset.
+    /// Returns `postcode_mismatch` with a suggestion if postcode, locality and canton don't belong together,
+    /// or `unknown_postcode` if neither the postcode nor the locality is known.
+    /// Without a dataset only the format is checked.
+    pub fn validate(&self, value: &Value) -> Result<SwissLocation, Violation> {
+        let (plz, locality, canton) = parse_location(value)?;
+        if self.is_empty() {
+            return Ok(SwissLocation {
+                plz,
+                locality,
+                canton: canton.unwrap_or_default(),
+            });
+        }
+
+        let key = comparable(&locality);
+        let same_canton = |entry: &&SwissLocation| {
+            canton
+                .as_ref()
+                .is_none_or(|canton| canton.eq_ignore_ascii_case(&entry.canton))
+        };
+        let candidates = self.0.get(&plz).map(Vec::as_slice).unwrap_or_default();
+        if let Some(found) = candidates
+            .iter()
+            .filter(same_canton)
+            .find(|entry| comparable(&entry.locality) == key)
+        {
+            return Ok(found.clone());
+        }
+
+        // did you mean: the locality with another postcode, or else the locality of the postcode
+        let suggestion = self
+            .0
+            .values()
+            .flatten()
+            .filter(same_canton)
+            .filter(|entry| comparable(&entry.locality) == key)
+            .min_by(|a, b| a.plz.cmp(&b.plz))
+            .or_else(|| candidates.first());
+        match suggestion {
+            Some(suggestion) => Err(Violation::PostcodeMismatch {
+                suggestion: suggestion.to_string(),
+            }),
+            None => Err(Violation::UnknownPostcode),
+        }
+    }
+}
+
+impl fmt::Debug for Postcodes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Postcodes({} postcodes)", self.0.len())
+    }
+}
+
+impl PartialEq for Postcodes {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0) || self.0 == other.0
+    }
+}
+
+/// Splits a location into postcode, locality and canton, the postcode has to be four digits.
+fn parse_location(value: &Value) -> Result<(String, String, Option<String>), Violation> {
+    let (plz, locality, canton) = match value {
+        Value::String(text) => {
+            let text = text.trim();
+            let (plz, locality) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+            (plz.to_string(), locality.trim().to_string(), None)
+        }
+        Value::Object(location) => {
+            let text = |key: &str| location.get(key).and_then(Value::as_str).map(str::trim);
+            (
+                text("plz").unwrap_or_default().to_string(),
+                text("locality").unwrap_or_default().to_string(),
+                text("canton")
+                    .filter(|canton| !canton.is_empty())
+                    .map(str::to_string),
+            )
+        }
+        _ => return Err(Violation::BadFormat),
+    };
+    if plz.len() != 4 || !plz.chars().all(|c| c.is_ascii_digit()) || locality.is_empty() {
+        return Err(Violation::BadFormat);
+    }
+    Ok((plz, locality, canton))
+}
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn postcodes() -> Postcodes {
+        Postcodes::load("data/postcodes.sample.csv").unwrap()
+    }
+
+    #[test]
+    fn locations_are_spelled_like_in_the_dataset() {
+        let expected = Ok(SwissLocation {
+            plz: "8001".to_string(),
+            locality: "Zürich".to_string(),
+            canton: "ZH".to_string(),
+        });
+        assert_eq!(postcodes().validate(&json!("8001 zurich")), expected);
+        assert_eq!(
+            postcodes().validate(&json!({"plz": "8001", "locality": "ZÜRICH", "canton": "zh"})),
+            expected
+        );
+        assert_eq!(
+            postcodes().validate(&json!("800 Zürich")),
+            Err(Violation::BadFormat)
+        );
+    }
+
+    #[test]
+    fn mismatches_get_a_suggestion() {
+        // the locality is known with another postcode
+        assert_eq!(
+            postcodes().validate(&json!("8001 Bern")),
+            Err(Violation::PostcodeMismatch {
+                suggestion: "3011 Bern (BE)".to_string()
+            })
+        );
+        // the postcode is known with another locality
+        assert_eq!(
+            postcodes().validate(&json!("3011 Nowhere")),
+            Err(Violation::PostcodeMismatch {
+                suggestion: "3011 Bern (BE)".to_string()
+            })
+        );
+        // the canton doesn't match
+        assert_eq!(
+            postcodes().validate(&json!({"plz": "3011", "locality": "Bern", "canton": "ZH"})),
+            Err(Violation::PostcodeMismatch {
+                suggestion: "3011 Bern (BE)".to_string()
+            })
+        );
+        assert_eq!(
+            postcodes().validate(&json!("9999 Nowhere")),
+            Err(Violation::UnknownPostcode)
+        );
+    }
+
+    #[test]
+    fn without_a_dataset_only_the_format_is_checked() {
+        assert_eq!(
+            Postcodes::default().validate(&json!("9999 Nowhere")),
+            Ok(SwissLocation {
+                plz: "9999".to_string(),
+                locality: "Nowhere".to_string(),
+                canton: String::new(),
+            })
+        );
+    }
+}
@@ -0,0 +1,463 @@
+//! This module contains the rule engine. It evaluates a JSON document against the fields declared in the config,
+//! so new fields only need a `<field>` declaration in the XML file.
+use crate::{
+    library::{
+        lib::{
+            parse_date, text_length, today, validate_ahv, validate_email, validate_iban,
+            validate_phone, validate_uid,
+        },
+        normalizer::derived_age,
+    },
+    models::{
+        validation_models::{ValidationReport, Violation},
+        xml_models::{Condition, Config, Field, FieldType, LengthUnit},
+    },
+};
+use serde_json::{Map, Value};
+
+///# Evaluates a document
+///
+/// Checks every declared field and every cross-field rule of the document.
+/// Fields which are not declared are reported as `unknown_field`, the `_id` key is ignored.
+/// `null` counts as a missing value.
+pub fn evaluate(config: &Config, document: &Map<String, Value>) -> Result<(), ValidationReport> {
+    let mut report = ValidationReport::default();
+    check_fields(config, document, &mut report);
+    check_age(config, document, &mut report);
+    check_rules(config, document, &mut report);
+    report.into_result()
+}
+
+fn check_fields(config: &Config, document: &Map<String, Value>, report: &mut ValidationReport) {
+    let rules = config.validation_rules.person.field_rules();
+
+    for rule in &rules {
+        match document.get(&rule.name) {
+            None | Some(Value::Null) => {
+                if rule.required {
+                    report.add(&rule.name, Violation::Required);
+                }
+            }
+            Some(value) => {
+                if let Err(violation) = check_field(config, rule, value) {
+                    report.add(&rule.name, violation);
+                }
+            }
+        }
+    }
+
+    for key in document.keys() {
+        if key != "_id" && !rules.iter().any(|rule| &rule.name == key) {
+            report.add(key, Violation::UnknownField);
+        }
+    }
+}
+
+///# Checks the age against the date of birth
+///
+/// If the config derives the age and both are given, the age has to be the one derived from the date of birth,
+/// otherwise `age_mismatch` is reported.
+fn check_age(config: &Config, document: &Map<String, Value>, report: &mut ValidationReport) {
+    let derived_from = match &config.validation_rules.person.age.derived_from {
+        Some(derived_from) => derived_from,
+        None => return,
+    };
+    // an invalid date of birth is already reported
+    if report
+        .errors
+        .iter()
+        .any(|error| &error.field == derived_from)
+    {
+        return;
+    }
+    let derived = match derived_age(config, document) {
+        Some(derived) => derived,
+        None => return,
+    };
+    let age = match document.get("age") {
+        Some(Value::Number(number)) => number.as_i64(),
+        Some(Value::String(text)) => text.trim().parse::<i64>().ok(),
+        _ => None,
+    };
+    if age.is_some_and(|age| age != derived as i64) {
+        report.add("age", Violation::AgeMismatch { age: derived });
+    }
+}
+
+///# Checks the cross-field rules
+///
+/// A rule applies if all of its `<when>` conditions hold, every `<then>` condition which doesn't hold
+/// is reported as `rule_violated` on the field of the condition.
+fn check_rules(config: &Config, document: &Map<String, Value>, report: &mut ValidationReport) {
+    for rule in &config.validation_rules.person.rules {
+        let applies = rule
+            .when
+            .iter()
+            .all(|condition| holds(condition, document).unwrap_or(false));
+        if !applies {
+            continue;
+        }
+        for condition in &rule.then {
+            // a missing value has nothing to check, required fields are reported by the field checks
+            if !holds(condition, document).unwrap_or(true) {
+                report.add(
+                    &condition.field,
+                    Violation::RuleViolated {
+                        rule: rule.name.clone(),
+                    },
+                );
+            }
+        }
+    }
+}
+
+///# Checks a single condition
+///
+/// Returns `None` if the field is missing in the document.
+/// Structured values like a Swiss location hold if one of their members does, e.g. `equals="Zürich"` for the locality.
+fn holds(condition: &Condition, document: &Map<String, Value>) -> Option<bool> {
+    let values = match document.get(&condition.field)? {
+        Value::Null => return None,
+        Value::Object(members) => members.values().map(condition_text).collect(),
+        value => vec![condition_text(value)],
+    };
+    let number = values.iter().find_map(|value| value.parse::<i64>().ok());
+
+    let equals = condition.equals.as_ref().is_none_or(|equals| {
+        values
+            .iter()
+            .any(|value| equals.to_lowercase() == value.to_lowercase())
+    });
+    let contains = condition.contains.as_ref().is_none_or(|contains| {
+        values
+            .iter()
+            .any(|value| value.to_lowercase().contains(&contains.to_lowercase()))
+    });
+    let min_value = condition
+        .min_value
+        .is_none_or(|min| number.is_some_and(|number| number >= min));
+    let max_value = condition
+        .max_value
+        .is_none_or(|max| number.is_some_and(|number| number <= max));
+    Some(equals && contains && min_value && max_value)
+}
+
+fn condition_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.trim().to_string(),
+        value => value.to_string(),
+    }
+}
+
+///# Checks a single value against its declaration
+fn check_field(config: &Config, rule: &Field, value: &Value) -> Result<(), Violation> {
+    match rule.type_ {
+        FieldType::String | FieldType::Regex => {
+            let text = as_text(value)?;
+            check_length(text, rule.min, rule.max, rule.unit)?;
+            check_pattern(config, text, &rule.pattern)?;
+            check_allowed(text, &rule.allowed)
+        }
+        FieldType::Integer => check_integer(value, rule.min, rule.max),
+        FieldType::Email => {
+            validate_email(as_text(value)?, &config.validation_rules.person.email).map(|_| ())
+        }
+        FieldType::Ahv => validate_ahv(as_text(value)?).map(|_| ()),
+        FieldType::Enum => match value {
+            Value::Number(number) => check_allowed(&number.to_string(), &rule.allowed),
+            value => check_allowed(as_text(value)?, &rule.allowed),
+        },
+        FieldType::SwissLocation => config.postcodes.validate(value).map(|_| ()),
+        FieldType::Occupation => config.occupations.validate(value).map(|_| ()),
+        FieldType::Date => check_date(as_text(value)?, rule),
+        FieldType::Uid => validate_uid(as_text(value)?).map(|_| ()),
+        FieldType::Iban => validate_iban(as_text(value)?).map(|_| ()),
+        FieldType::Phone => {
+            validate_phone(as_text(value)?, &config.validation_rules.person.phone).map(|_| ())
+        }
+    }
+}
+
+fn as_text(value: &Value) -> Result<&str, Violation> {
+    value.as_str().ok_or(Violation::BadFormat)
+}
+
+///# Checks the length of a text
+///
+/// Returns `too_short` or `too_long` if the length of `value`, measured in `unit`, is not within `min..=max`.
+fn check_length(
+    value: &str,
+    min: Option<u32>,
+    max: Option<u32>,
+    unit: LengthUnit,
+) -> Result<(), Violation> {
+    let length = text_length(value, unit);
+    if let Some(min) = min {
+        if length < min as usize {
+            return Err(Violation::TooShort { min });
+        }
+    }
+    if let Some(max) = max {
+        if length > max as usize {
+            return Err(Violation::TooLong { max });
+        }
+    }
+    Ok(())
+}
+
+///# Checks a text against an optional regular expression
+///
+/// The pattern has to match the whole value, the compiled expression is taken from the config.
+fn check_pattern(config: &Config, value: &str, pattern: &Option<String>) -> Result<(), Violation> {
+    match pattern {
+        Some(pattern) if !config.patterns.is_match(pattern, value) => {
+            Err(Violation::PatternMismatch)
+        }
+        _ => Ok(()),
+    }
+}
+
+///# Checks a text against an optional list of allowed values
+///
+/// The comparison is case insensitive, an empty list allows every value.
+fn check_allowed(value: &str, allowed: &[String]) -> Result<(), Violation> {
+    if !allowed.is_empty()
+        && !allowed
+            .iter()
+            .any(|allowed| allowed.trim().to_lowercase() == value.trim().to_lowercase())
+    {
+        return Err(Violation::NotAllowed);
+    }
+    Ok(())
+}
+
+///# Checks a date
+///
+/// Returns `bad_format` if the value is no date, `too_early` or `too_late` if it is not within `minDate..=maxDate`.
+/// Fields with `past` don't accept dates after today.
+fn check_date(value: &str, rule: &Field) -> Result<(), Violation> {
+    let date = parse_date(value).ok_or(Violation::BadFormat)?;
+    if let Some(min) = rule.min_date {
+        if date < min {
+            return Err(Violation::TooEarly {
+                min: min.to_string(),
+            });
+        }
+    }
+    let max = match (rule.max_date, rule.past) {
+        (Some(max), true) => Some(max.min(today())),
+        (None, true) => Some(today()),
+        (max, false) => max,
+    };
+    if let Some(max) = max {
+        if date > max {
+            return Err(Violation::TooLate {
+                max: max.to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+///# Checks a numeric value
+///
+/// Accepts JSON numbers and numeric strings. Returns `not_a_number` if the value is no whole number,
+/// or `out_of_range` if it is not within `min..=max`.
+fn check_integer(value: &Value, min: Option<u32>, max: Option<u32>) -> Result<(), Violation> {
+    let number = match value {
+        Value::Number(number) => number.as_i64(),
+        Value::String(text) => text.trim().parse::<i64>().ok(),
+        _ => None,
+    };
+    let number = match number {
+        Some(number) => number,
+        None => return Err(Violation::NotANumber),
+    };
+
+    let (min, max) = (min.unwrap_or(0), max.unwrap_or(u32::MAX));
+    if number < min as i64 || number > max as i64 {
+        return Err(Violation::OutOfRange { min, max });
+    }
+    Ok(())
+}
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{library::lib::age_on, models::validation_models::ValidationError};
+    use serde_json::json;
+
+    fn config(person: &str) -> Config {
+        Config::from_xml(&format!(
+            "<config><validationRules><person>{}</person></validationRules></config>",
+            person
+        ))
+        .unwrap()
+    }
+
+    fn violations(config: &Config, document: Value) -> Vec<(String, Violation)> {
+        let document = document.as_object().cloned().unwrap_or_default();
+        let mut report = ValidationReport::default();
+        check_fields(config, &document, &mut report);
+        check_rules(config, &document, &mut report);
+        report
+            .errors
+            .into_iter()
+            .map(|error| (error.field, error.violation))
+            .collect()
+    }
+
+    /// Only the additional fields, the built-in ones are not part of the checked documents
+    fn fields(config: &Config, document: Value) -> Vec<(String, Violation)> {
+        let builtin = Config::default().validation_rules.person.field_rules();
+        violations(config, document)
+            .into_iter()
+            .filter(|(field, _)| !builtin.iter().any(|rule| &rule.name == field))
+            .collect()
+    }
+
+    #[test]
+    fn undeclared_fields_are_unknown() {
+        let config = config("");
+        let found = violations(&config, json!({"_id": "1", "nickname": "Jo"}));
+        assert!(found.contains(&("nickname".to_string(), Violation::UnknownField)));
+        assert!(!found.iter().any(|(field, _)| field == "_id"));
+    }
+
+    #[test]
+    fn required_fields_have_to_be_present() {
+        let config = config(r#"<field name="code" type="string" required="true" />"#);
+        assert_eq!(
+            fields(&config, json!({ "code": null })),
+            vec![("code".to_string(), Violation::Required)]
+        );
+        assert!(fields(&config, json!({ "code": "A" })).is_empty());
+        assert!(violations(&config, json!({}))
+            .contains(&("first_name".to_string(), Violation::Required)));
+    }
+
+    #[test]
+    fn integers_are_checked_against_their_range() {
+        let config = config(r#"<field name="level" type="integer" min="1" max="5" />"#);
+        assert!(fields(&config, json!({ "level": 3 })).is_empty());
+        assert!(fields(&config, json!({ "level": " 5 " })).is_empty());
+        assert_eq!(
+            fields(&config, json!({ "level": 6 })),
+            vec![(
+                "level".to_string(),
+                Violation::OutOfRange { min: 1, max: 5 }
+            )]
+        );
+        assert_eq!(
+            fields(&config, json!({ "level": "three" })),
+            vec![("level".to_string(), Violation::NotANumber)]
+        );
+    }
+
+    #[test]
+    fn enums_only_accept_their_values() {
+        let config = config(
+            r#"<field name="status" type="enum"><value>active</value><value>2</value></field>"#,
+        );
+        assert!(fields(&config, json!({ "status": "Active" })).is_empty());
+        assert!(fields(&config, json!({ "status": 2 })).is_empty());
+        assert_eq!(
+            fields(&config, json!({ "status": "deleted" })),
+            vec![("status".to_string(), Violation::NotAllowed)]
+        );
+    }
+
+    #[test]
+    fn regex_fields_have_to_match_the_whole_value() {
+        let config = config(r#"<field name="code" type="regex" pattern="[A-Z]{2}\d{4}" />"#);
+        assert!(fields(&config, json!({ "code": "AB1234" })).is_empty());
+        assert_eq!(
+            fields(&config, json!({ "code": "xAB1234" })),
+            vec![("code".to_string(), Violation::PatternMismatch)]
+        );
+        assert_eq!(
+            fields(&config, json!({ "code": 1234 })),
+            vec![("code".to_string(), Violation::BadFormat)]
+        );
+    }
+
+    #[test]
+    fn then_conditions_apply_when_all_when_conditions_hold() {
+        let config = config(
+            r#"<rule name="apprentice_age">
+                <when field="occupation" contains="lernende" />
+                <then field="age" maxValue="24" />
+            </rule>
+            <rule name="minor_pensum">
+                <when field="age" maxValue="17" />
+                <when field="location" equals="Bern" />
+                <then field="pensum" maxValue="80" />
+            </rule>"#,
+        );
+        let rule = |name: &str| Violation::RuleViolated {
+            rule: name.to_string(),
+        };
+        let rules = |document: Value| {
+            violations(&config, document)
+                .into_iter()
+                .filter(|(_, violation)| matches!(violation, Violation::RuleViolated { .. }))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            rules(json!({ "occupation": "Lernende Informatik", "age": 30 })),
+            vec![("age".to_string(), rule("apprentice_age"))]
+        );
+        assert!(rules(json!({ "occupation": "Lernende Informatik", "age": 20 })).is_empty());
+        assert!(rules(json!({ "occupation": "Informatiker", "age": 30 })).is_empty());
+        // a missing value is not checked by the rule
+        assert!(rules(json!({ "occupation": "Lernende Informatik" })).is_empty());
+
+        assert_eq!(
+            rules(json!({ "age": 16, "location": "bern", "pensum": 100 })),
+            vec![("pensum".to_string(), rule("minor_pensum"))]
+        );
+        assert!(rules(json!({ "age": 16, "location": "Basel", "pensum": 100 })).is_empty());
+        // members of structured values are compared one by one
+        assert_eq!(
+            rules(json!({
+                "age": 16,
+                "location": { "plz": "3011", "locality": "Bern", "canton": "BE" },
+                "pensum": 100
+            })),
+            vec![("pensum".to_string(), rule("minor_pensum"))]
+        );
+    }
+
+    #[test]
+    fn the_age_is_checked_against_the_configured_date() {
+        let age_of = |config: &Config, document: Value| {
+            let document = document.as_object().cloned().unwrap_or_default();
+            let mut report = ValidationReport::default();
+            check_age(config, &document, &mut report);
+            report.errors
+        };
+        let age = age_on(parse_date("1990-05-17").unwrap(), today());
+        let derived = config(
+            r#"<age type="integer" minValue="12" maxValue="120" derivedFrom="birthday" />
+            <field name="birthday" type="date" past="true" />"#,
+        );
+        assert!(age_of(&derived, json!({"age": age, "birthday": "17.05.1990"})).is_empty());
+        assert_eq!(
+            age_of(&derived, json!({"age": age + 1, "birthday": "1990-05-17"})),
+            vec![ValidationError {
+                field: "age".to_string(),
+                violation: Violation::AgeMismatch { age },
+            }]
+        );
+        // without derivedFrom the age is independent of any date
+        let independent = config("");
+        assert!(age_of(
+            &independent,
+            json!({"age": 12, "date_of_birth": "1990-05-17"})
+        )
+        .is_empty());
+    }
+}
@@ -1,19 +1,22 @@
 //! This module is build upon the [notify](https://docs.rs/notify/latest/notify/) crate. It is used to read values from a xml file which will be used as validation settings.
 
-use crate::AppState;
+use crate::{
+    models::xml_models::{Config, ConfigError},
+    AppState,
+};
 use actix_web::web::Data;
 use futures::{
     channel::mpsc::{channel, Receiver},
     SinkExt, StreamExt,
 };
 use notify::{Config as Nconfig, Event, RecommendedWatcher, RecursiveMode, Watcher};
-use quick_xml::de::from_str;
 
 ///# Reads the xml file
 ///
 /// This function reads the xml file and returns a Result with the config struct or an error.
 /// The config struct is used as validation rules.
-pub fn read_xml(file: &str, state: Data<AppState>) -> Result<(), quick_xml::DeError> {
+/// The patterns are compiled before the config is replaced, if the file is invalid the previous config stays active.
+pub fn read_xml(file: &str, state: Data<AppState>) -> Result<(), ConfigError> {
     let xml = std::fs::read_to_string(file);
     let xml = match xml {
         Ok(xml) => xml,
@@ -23,10 +26,12 @@ pub fn read_xml(file: &str, state: Data<AppState>) -> Result<(), quick_xml::DeEr
         }
     };
 
+    let new_config = Config::from_xml(&xml)?;
+
     let config = state.valid_config.lock();
     match config {
         Ok(mut valid_config) => {
-            *valid_config = from_str(&xml)?;
+            *valid_config = new_config;
             dbg!(&valid_config);
         }
         Err(e) => {
@@ -67,12 +72,15 @@ pub async fn async_watch(file: &str, state: Data<AppState>) -> notify::Result<()
 
     while let Some(res) = rx.next().await {
         match res {
-            Ok(_event) => match read_xml(&file, state.clone()) {
+            Ok(_event) => match read_xml(file, state.clone()) {
                 Ok(_) => {
                     dbg!("ok");
                 }
                 Err(e) => {
-                    dbg!(e);
+                    eprintln!(
+                        "Rejected changes of {}, keeping the previous config: {}",
+                        file, e
+                    );
                 }
             },
             Err(e) => {
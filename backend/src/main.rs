@@ -5,14 +5,23 @@ mod repository;
 
 use actix_cors::Cors;
 use actix_web::{get, http::header, middleware::Logger, web::Data, App, HttpServer, Responder};
-use api::user_api::{create_user, delete_user, get_all_users, get_user, update_user};
+use api::{
+    batch_api::{import_csv, import_users, validate_batch},
+    export_api::export_users,
+    occupation_api::search_occupations,
+    user_api::{
+        create_user, delete_user, get_all_users, get_user, update_user, validate_new_user,
+        validate_user_update,
+    },
+};
+use library::{batch::ImportMode, csv_import::import_file};
 use models::xml_models::Config;
-use repository::mongodb_repo::MongoRepo;
+use repository::{migrations::run_migrations, mongodb_repo::MongoRepo};
 use std::sync::{Arc, Mutex};
 
 #[get("/")]
 async fn health() -> impl Responder {
-    format!("Server is up and running")
+    "Server is up and running".to_string()
 }
 
 /// # The AppState struct
@@ -23,6 +32,14 @@ pub struct AppState {
     pub valid_config: Arc<Mutex<Config>>,
 }
 
+/// Returns a copy of the current config.
+fn current_config(state: &AppState) -> Config {
+    match state.valid_config.lock() {
+        Ok(config) => config.clone(),
+        Err(e) => e.into_inner().clone(),
+    }
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     env_logger::init();
@@ -33,18 +50,59 @@ async fn main() -> std::io::Result<()> {
     // File of the XML document with the validations values
     let file = "valid.xml";
 
-    match library::xml_live_reader::read_xml(&file, state.clone()) {
+    match library::xml_live_reader::read_xml(file, state.clone()) {
         Ok(_) => {
             dbg!("XML file read successfully");
         }
         Err(e) => {
-            dbg!(e);
+            eprintln!("Could not load {}: {}", file, e);
         }
     }
 
-    actix_rt::spawn(library::xml_live_reader::async_watch(&file, state.clone()));
-
     let db = MongoRepo::init().await;
+
+    // `migrate [--dry-run]` only runs the migrations and prints the report
+    let mut args = std::env::args().skip(1);
+    match args.next().as_deref() {
+        Some("migrate") => {
+            let dry_run = args.any(|arg| arg == "--dry-run");
+            let report = run_migrations(db.database(), &current_config(&state), dry_run).await?;
+            print!("{}", report);
+            return Ok(());
+        }
+        // `import <file.csv> [--best-effort] [--errors <errors.csv>]` only imports the CSV file
+        Some("import") => {
+            let path = match args.next() {
+                Some(path) => path,
+                None => {
+                    eprintln!("Usage: import <file.csv> [--best-effort] [--errors <errors.csv>]");
+                    return Ok(());
+                }
+            };
+            let mut mode = ImportMode::Atomic;
+            let mut errors_path = format!("{}.errors.csv", path.trim_end_matches(".csv"));
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--best-effort" => mode = ImportMode::BestEffort,
+                    "--errors" => errors_path = args.next().unwrap_or(errors_path),
+                    _ => eprintln!("Unknown argument {}", arg),
+                }
+            }
+            let summary =
+                import_file(&db, current_config(&state), &path, &errors_path, mode).await?;
+            print!("{}", summary);
+            return Ok(());
+        }
+        _ => {}
+    }
+    match run_migrations(db.database(), &current_config(&state), false).await {
+        Ok(report) if !report.pending.is_empty() => print!("{}", report),
+        Ok(_) => {}
+        Err(e) => eprintln!("Could not migrate the users: {}", e),
+    }
+
+    actix_rt::spawn(library::xml_live_reader::async_watch(file, state.clone()));
+
     let db_data = Data::new(db);
     HttpServer::new(move || {
         let cors = Cors::default()
@@ -55,16 +113,24 @@ async fn main() -> std::io::Result<()> {
                 header::CONTENT_TYPE,
                 header::AUTHORIZATION,
                 header::ACCEPT,
+                header::ACCEPT_LANGUAGE,
             ]);
         App::new()
             .app_data(state.clone())
             .app_data(db_data.clone())
             .service(health)
             .service(create_user)
+            .service(validate_new_user)
+            .service(validate_user_update)
+            .service(validate_batch)
+            .service(import_users)
+            .service(import_csv)
             .service(get_user)
             .service(update_user)
             .service(delete_user)
             .service(get_all_users)
+            .service(export_users)
+            .service(search_occupations)
             .wrap(cors)
             .wrap(Logger::default())
     })
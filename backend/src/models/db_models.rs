@@ -1,101 +1,209 @@
-use crate::library::lib::{validate_update, validate_user};
+use crate::library::{
+    lib::{age_on, parse_date, today, validate_update, validate_user},
+    normalizer::{apply_defaults, derive_age, derived_age, normalize},
+};
+use chrono::NaiveDate;
 use mongodb::bson::doc;
 use mongodb::bson::oid::ObjectId;
-use serde::{Deserialize, Serialize};
+use serde::{de, Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
 
-use super::xml_models::Config;
+use super::{
+    domain_models::{AhvNumber, EmailAddress, Location, Occupation, PhoneNumber},
+    validation_models::{ValidationReport, Violation},
+    xml_models::Config,
+};
 
+/// # Summary
+/// A validated user.
+///
+/// The fields are private, a new user can only be created with [`User::from_document`].
+/// Deserializing is only used to read the stored users back from the database.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct User {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
-    pub id: Option<ObjectId>,
-    pub first_name: String,
-    pub last_name: String,
-    pub age: String,
-    pub pensum: String,
-    pub location: String,
-    pub occupation: String,
-    pub ahv_nr: String,
+    id: Option<ObjectId>,
+    first_name: String,
+    last_name: String,
+    #[serde(deserialize_with = "string_or_number")]
+    age: u8,
+    /// Pensum in percent
+    #[serde(deserialize_with = "string_or_number")]
+    pensum: u8,
+    location: Location,
+    occupation: Occupation,
+    ahv_nr: AhvNumber,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    email: Option<EmailAddress>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    phone: Option<PhoneNumber>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    date_of_birth: Option<NaiveDate>,
+    /// Values of the additional fields declared with `<field>` in the config
+    #[serde(flatten)]
+    custom_fields: Map<String, Value>,
 }
 
 impl User {
-    pub fn new(
-        config: Config,
-        first_name: String,
-        last_name: String,
-        age: String,
-        pensum: String,
-        location: String,
-        occupation: String,
-        ahv_nr: String,
-    ) -> Result<Self, String> {
-        validate_user(
-            config,
-            &first_name,
-            &last_name,
-            &age,
-            &pensum,
-            &location,
-            &occupation,
-            &ahv_nr,
-        )?;
-        Ok(User {
-            id: None,
-            first_name,
-            last_name,
-            age,
-            pensum,
-            location,
-            occupation,
-            ahv_nr,
-        })
+    ///# Creates a user from a JSON object
+    ///
+    /// Omitted fields with a default in the config get their default, then the object is normalized, the age is derived
+    /// from the date of birth and the object is validated against the config, every failed check is returned in the report.
+    /// Returns the user together with the names of the fields which got their default.
+    pub fn from_document(
+        config: &Config,
+        mut document: Map<String, Value>,
+    ) -> Result<(Self, Vec<String>), ValidationReport> {
+        document.retain(|key, value| key != "_id" && !value.is_null());
+        let defaults_applied = apply_defaults(config, &mut document);
+        normalize(config, &mut document);
+        derive_age(config, &mut document);
+        validate_user(config, &document)?;
+        Ok((from_validated(document)?, defaults_applied))
+    }
+
+    pub fn id(&self) -> Option<ObjectId> {
+        self.id
+    }
+
+    pub fn ahv_nr(&self) -> &AhvNumber {
+        &self.ahv_nr
+    }
+
+    /// Sets the id under which the user is stored, `None` lets the database assign one.
+    pub(crate) fn with_id(self, id: Option<ObjectId>) -> User {
+        User { id, ..self }
+    }
+
+    ///# Derives the age from the date of birth again
+    ///
+    /// The stored age gets stale every year, users without a date of birth keep their age.
+    pub fn refresh_age(&mut self, config: &Config) {
+        let date_of_birth = match config.validation_rules.person.age.derived_from.as_deref() {
+            Some("date_of_birth") => self.date_of_birth,
+            Some(field) => self
+                .custom_fields
+                .get(field)
+                .and_then(Value::as_str)
+                .and_then(parse_date),
+            None => None,
+        };
+        if let Some(date_of_birth) = date_of_birth {
+            self.age = age_on(date_of_birth, today()).min(u8::MAX as u32) as u8;
+        }
     }
 }
 
+/// # Summary
+/// The validated fields of an update, created with [`UpdatedUser::from_document`].
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct UpdatedUser {
-    pub first_name: Option<String>,
-    pub last_name: Option<String>,
-    pub age: Option<String>,
-    pub pensum: Option<String>,
-    pub location: Option<String>,
-    pub occupation: Option<String>,
-    pub ahv_nr: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    first_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_name: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "optional_string_or_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    age: Option<u8>,
+    #[serde(
+        default,
+        deserialize_with = "optional_string_or_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pensum: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    location: Option<Location>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    occupation: Option<Occupation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ahv_nr: Option<AhvNumber>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    email: Option<EmailAddress>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    phone: Option<PhoneNumber>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    date_of_birth: Option<NaiveDate>,
+    /// Values of the additional fields declared with `<field>` in the config
+    #[serde(flatten)]
+    custom_fields: Map<String, Value>,
 }
 
 impl UpdatedUser {
-    pub fn new(
-        config: Config,
-        first_name: Option<String>,
-        last_name: Option<String>,
-        age: Option<String>,
-        pensum: Option<String>,
-        location: Option<String>,
-        occupation: Option<String>,
-        ahv_nr: Option<String>,
-    ) -> Result<Self, String> {
-        validate_update(
-            config,
-            &first_name,
-            &last_name,
-            &age,
-            &pensum,
-            &location,
-            &occupation,
-            &ahv_nr,
-        )?;
-        Ok(UpdatedUser {
-            first_name,
-            last_name,
-            age,
-            pensum,
-            location,
-            occupation,
-            ahv_nr,
-        })
+    ///# Creates an update from a JSON object
+    ///
+    /// Only the fields present in the object are updated, `null` values are ignored.
+    /// The fields are normalized, then the update is applied to `stored` and the result is validated like a new user.
+    /// If the user has a date of birth, the age is derived again and written with the update.
+    pub fn from_document(
+        config: &Config,
+        stored: &User,
+        mut document: Map<String, Value>,
+    ) -> Result<Self, ValidationReport> {
+        document.retain(|key, value| key != "_id" && !value.is_null());
+        normalize(config, &mut document);
+        let stored = match serde_json::to_value(stored) {
+            Ok(Value::Object(stored)) => stored,
+            _ => Map::new(),
+        };
+        // the stored age gets stale, it is derived again from the stored date of birth
+        let derived_from = config.validation_rules.person.age.derived_from.as_ref();
+        if derived_from.is_some_and(|field| !document.contains_key(field))
+            && !document.contains_key("age")
+        {
+            if let Some(age) = derived_age(config, &stored) {
+                document.insert("age".to_string(), Value::from(age));
+            }
+        }
+        // a new date of birth without an age gets the derived age
+        derive_age(config, &mut document);
+        validate_update(config, &stored, &document)?;
+        from_validated(document)
     }
 }
 
+/// Converts a validated JSON object into `T`.
+///
+/// The typed fields check their values again, e.g. [`EmailAddress`] its syntax.
+/// If they reject a value the rule engine accepted, the user is reported with `bad_format` instead of being stored.
+fn from_validated<T: de::DeserializeOwned>(
+    document: Map<String, Value>,
+) -> Result<T, ValidationReport> {
+    serde_json::from_value(Value::Object(document)).map_err(|error| {
+        dbg!(error);
+        let mut report = ValidationReport::default();
+        report.add("user", Violation::BadFormat);
+        report
+    })
+}
+
+/// Accepts `"22"` as well as `22`, the rule engine allows both for integer fields
+/// and users stored before the fields were typed keep their values as strings.
+fn string_or_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u8, D::Error> {
+    let number = match Value::deserialize(deserializer)? {
+        Value::String(text) => text.trim().parse::<u64>().ok(),
+        Value::Number(number) => number.as_u64(),
+        other => {
+            return Err(de::Error::custom(format!(
+                "expected a string or a number, got {}",
+                other
+            )))
+        }
+    };
+    number
+        .filter(|number| *number <= u8::MAX as u64)
+        .map(|number| number as u8)
+        .ok_or_else(|| de::Error::custom("expected a whole number from 0 to 255"))
+}
+
+fn optional_string_or_number<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<u8>, D::Error> {
+    string_or_number(deserializer).map(Some)
+}
+
 /// # Summary
 /// Used for String responses.
 /// # Examples
@@ -106,7 +214,49 @@ impl UpdatedUser {
 ///    message: "User successfully created!".to_string(),
 /// };
 /// ```
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MSG {
     pub message: String,
 }
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn emails_longer_than_the_default_limit_convert() {
+        let config = Config::from_xml(
+            r#"<config><validationRules><person>
+                <name type="string" min="1" max="50" />
+                <age type="integer" minValue="12" maxValue="120" />
+                <pensum type="integer" minValue="10" maxValue="100" />
+                <location type="string" min="2" max="55" />
+                <occupation type="string" min="2" max="55" />
+                <email type="email" maxLength="400" />
+            </person></validationRules></config>"#,
+        )
+        .unwrap();
+        let email = format!(
+            "{}@{}.example.com",
+            "a".repeat(64),
+            vec!["b".repeat(60); 4].join(".")
+        );
+        assert!(email.len() > 254);
+        let document = json!({
+            "first_name": "Anna",
+            "last_name": "Muster",
+            "age": 30,
+            "pensum": 80,
+            "location": "Bern",
+            "occupation": "Informatikerin",
+            "ahv_nr": "756.1718.4457.72",
+            "email": email,
+        });
+        let document = document.as_object().cloned().unwrap_or_default();
+        assert!(User::from_document(&config, document).is_ok());
+    }
+}
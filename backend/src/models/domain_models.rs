@@ -0,0 +1,262 @@
+//! This module contains the value types of a user. They can only be created through their validators,
+//! so a value of one of these types has always passed its checks.
+use crate::{
+    library::lib::{parse_phone, validate_ahv, validate_email, validate_iban, validate_uid},
+    models::xml_models::Email,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+/// # Summary
+/// A valid AHV number in its canonical notation `756.XXXX.XXXX.XX`.
+///
+/// Serialized as a plain string. Deserializing accepts every notation [`validate_ahv`] accepts.
+/// # Examples
+///
+/// ```
+/// use crate::library::lib::validate_ahv;
+/// let ahv_nr = validate_ahv("756 1718 4457 72").unwrap();
+/// assert_eq!(ahv_nr.to_string(), "756.1718.4457.72");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AhvNumber(String);
+
+impl AhvNumber {
+    /// Writes the 13 digits of a checked number as `756.XXXX.XXXX.XX`, only used by [`validate_ahv`].
+    pub(crate) fn from_digits(digits: &str) -> AhvNumber {
+        AhvNumber(format!(
+            "{}.{}.{}.{}",
+            &digits[0..3],
+            &digits[3..7],
+            &digits[7..11],
+            &digits[11..13]
+        ))
+    }
+
+    /// Hides the digits which identify the person, `756.1718.4457.72` becomes `756.****.****.72`.
+    pub fn masked(&self) -> String {
+        format!("756.****.****.{}", &self.0[14..])
+    }
+}
+
+impl fmt::Display for AhvNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Serialize for AhvNumber {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for AhvNumber {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        validate_ahv(&value).map_err(|violation| {
+            serde::de::Error::custom(format!("invalid AHV number {}: {:?}", value, violation))
+        })
+    }
+}
+
+/// # Summary
+/// A valid Swiss company identification number (UID) in its canonical notation `CHE-123.456.789`.
+///
+/// Serialized as a plain string. Deserializing accepts every notation [`validate_uid`] accepts.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Uid(String);
+
+impl Uid {
+    /// Writes the 9 digits of a checked number as `CHE-XXX.XXX.XXX`, only used by [`validate_uid`].
+    pub(crate) fn from_digits(digits: &str) -> Uid {
+        Uid(format!(
+            "CHE-{}.{}.{}",
+            &digits[0..3],
+            &digits[3..6],
+            &digits[6..9]
+        ))
+    }
+}
+
+impl fmt::Display for Uid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Serialize for Uid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Uid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        validate_uid(&value).map_err(|violation| {
+            serde::de::Error::custom(format!("invalid UID {}: {:?}", value, violation))
+        })
+    }
+}
+
+/// # Summary
+/// A valid Swiss or Liechtenstein IBAN in the electronic notation `CH9300762011623852957`.
+///
+/// Serialized as a plain string. Deserializing accepts every notation [`validate_iban`] accepts.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Iban(String);
+
+impl Iban {
+    /// Wraps a checked IBAN without spaces in upper case, only used by [`validate_iban`].
+    pub(crate) fn from_compact(compact: &str) -> Iban {
+        Iban(compact.to_string())
+    }
+}
+
+impl fmt::Display for Iban {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Serialize for Iban {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Iban {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        validate_iban(&value).map_err(|violation| {
+            serde::de::Error::custom(format!("invalid IBAN {}: {:?}", value, violation))
+        })
+    }
+}
+
+/// # Summary
+/// A syntactically valid email address which passed the `<email>` settings of the config.
+///
+/// Serialized as a plain string. Deserializing only checks the syntax, the settings of the config are checked by [`validate_email`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EmailAddress(String);
+
+impl EmailAddress {
+    /// Wraps a checked address, only used by [`validate_email`].
+    pub(crate) fn new(email: &str) -> EmailAddress {
+        EmailAddress(email.to_string())
+    }
+}
+
+impl fmt::Display for EmailAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Serialize for EmailAddress {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for EmailAddress {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        // only the syntax, a config may allow longer addresses than the default settings
+        let syntax = Email {
+            max_length: u32::MAX,
+            ..Email::default()
+        };
+        validate_email(&value, &syntax).map_err(|violation| {
+            serde::de::Error::custom(format!("invalid email address {}: {:?}", value, violation))
+        })
+    }
+}
+
+/// # Summary
+/// A phone number in the international E.164 notation `+41791234567`, which passed the `<phone>` settings of the config.
+///
+/// Serialized as a plain string. Deserializing only checks the notation with [`parse_phone`],
+/// the allowed country codes of the config are checked by [`validate_phone`](crate::library::lib::validate_phone).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PhoneNumber(String);
+
+impl PhoneNumber {
+    /// Writes the digits of a checked number with the leading `+`, only used by [`parse_phone`].
+    pub(crate) fn from_digits(digits: &str) -> PhoneNumber {
+        PhoneNumber(format!("+{}", digits))
+    }
+
+    /// The digits of the number without the leading `+`, starting with the country code.
+    pub fn digits(&self) -> &str {
+        &self.0[1..]
+    }
+}
+
+impl fmt::Display for PhoneNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Serialize for PhoneNumber {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for PhoneNumber {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        parse_phone(&value).map_err(|violation| {
+            serde::de::Error::custom(format!("invalid phone number {}: {:?}", value, violation))
+        })
+    }
+}
+
+/// # Summary
+/// A Swiss locality from the postcode dataset, see [`Postcodes`](crate::library::postcodes::Postcodes).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SwissLocation {
+    pub plz: String,
+    pub locality: String,
+    pub canton: String,
+}
+
+impl fmt::Display for SwissLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} ({})", self.plz, self.locality, self.canton)
+    }
+}
+
+/// # Summary
+/// The location of a user.
+///
+/// Free text, or a [`SwissLocation`] if `<location postcodes="..." />` is set in the config.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Location {
+    Swiss(SwissLocation),
+    Text(String),
+}
+
+/// # Summary
+/// An occupation from the code list, see [`Occupations`](crate::library::occupations::Occupations).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OccupationCode {
+    pub code: String,
+    pub label: String,
+}
+
+/// # Summary
+/// The occupation of a user.
+///
+/// Free text, or an [`OccupationCode`] if `<occupation codes="..." />` is set in the config.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Occupation {
+    Coded(OccupationCode),
+    Text(String),
+}
@@ -1,2 +1,4 @@
 pub mod db_models;
+pub mod domain_models;
+pub mod validation_models;
 pub mod xml_models;
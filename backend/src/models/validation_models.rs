@@ -0,0 +1,88 @@
+use serde::Serialize;
+
+/// # Summary
+/// The rule a value violated, together with the parameters of the rule.
+///
+/// Serialized as `{"code": "out_of_range", "params": {"min": 12, "max": 120}}` so clients can render their own text.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(tag = "code", content = "params", rename_all = "snake_case")]
+pub enum Violation {
+    TooShort { min: u32 },
+    TooLong { max: u32 },
+    OutOfRange { min: u32, max: u32 },
+    NotANumber,
+    BadFormat,
+    BadChecksum,
+    Required,
+    DomainNotAllowed { domain: String },
+    IdnNotAllowed,
+    PlusNotAllowed,
+    PatternMismatch,
+    NotAllowed,
+    UnknownField,
+    RuleViolated { rule: String },
+    UnknownPostcode,
+    PostcodeMismatch { suggestion: String },
+    UnknownOccupation,
+    TooEarly { min: String },
+    TooLate { max: String },
+    AgeMismatch { age: u32 },
+}
+
+/// # Summary
+/// A single failed check on one field of a user.
+/// # Examples
+///
+/// ```
+/// use crate::library::messages::{message, Language};
+/// use crate::models::validation_models::{ValidationError, Violation};
+/// let error = ValidationError {
+///     field: "age".to_string(),
+///     violation: Violation::OutOfRange { min: 12, max: 120 },
+/// };
+/// assert_eq!(message(&error, Language::En), "Age must be between 12 and 120.");
+/// ```
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ValidationError {
+    pub field: String,
+    #[serde(flatten)]
+    pub violation: Violation,
+}
+
+/// # Summary
+/// Collects every failed check of a validation run, so all invalid fields can be reported at once.
+/// # Examples
+///
+/// ```
+/// use crate::models::validation_models::{ValidationReport, Violation};
+/// let mut report = ValidationReport::default();
+/// report.add("first_name", Violation::TooShort { min: 1 });
+/// assert!(report.into_result().is_err());
+/// ```
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct ValidationReport {
+    pub errors: Vec<ValidationError>,
+}
+
+impl ValidationReport {
+    /// Records a failed check for `field`.
+    pub fn add(&mut self, field: &str, violation: Violation) {
+        self.errors.push(ValidationError {
+            field: field.to_string(),
+            violation,
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Turns the report into `Ok(())` if no check failed, otherwise into `Err(self)`.
+    pub fn into_result(self) -> Result<(), ValidationReport> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
@@ -1,4 +1,8 @@
+use crate::library::{occupations::Occupations, postcodes::Postcodes};
+use chrono::NaiveDate;
+use regex::Regex;
 use serde::Deserialize;
+use std::{collections::HashMap, fmt};
 
 // ----------------- DefaultSettings -----------------
 
@@ -10,6 +14,178 @@ pub struct Config {
     pub default_settings: DefaultSettings,
     #[serde(rename = "validationRules")]
     pub validation_rules: ValidationRules,
+    #[serde(rename = "csvImport")]
+    pub csv_import: CsvImport,
+    /// The compiled `pattern` attributes, filled by [`Config::compile`]
+    #[serde(skip)]
+    pub patterns: Patterns,
+    /// The dataset of `location@postcodes`, loaded by [`Config::compile`]
+    #[serde(skip)]
+    pub postcodes: Postcodes,
+    /// The code list of `occupation@codes`, loaded by [`Config::compile`]
+    #[serde(skip)]
+    pub occupations: Occupations,
+}
+
+impl Config {
+    ///# Parses and prepares a config
+    ///
+    /// Deserializes the XML document, compiles all `pattern` attributes and loads the referenced datasets,
+    /// so an invalid pattern or a missing file is reported here and not when a request is validated.
+    pub fn from_xml(xml: &str) -> Result<Config, ConfigError> {
+        let mut config: Config = quick_xml::de::from_str(xml).map_err(ConfigError::Xml)?;
+        config.compile()?;
+        Ok(config)
+    }
+
+    ///# Compiles the patterns of all fields and loads the datasets
+    pub fn compile(&mut self) -> Result<(), ConfigError> {
+        let person = &self.validation_rules.person;
+        // age and pensum are stored as u8
+        for (field, max_value) in [("age", person.age.max), ("pensum", person.pensum.max_value)] {
+            if max_value > u8::MAX as u32 {
+                return Err(ConfigError::TooLarge {
+                    field: field.to_string(),
+                    max: u8::MAX as u32,
+                });
+            }
+        }
+
+        if let Some(derived_from) = &person.age.derived_from {
+            let is_date = person
+                .field_rules()
+                .iter()
+                .any(|field| &field.name == derived_from && field.type_ == FieldType::Date);
+            if !is_date {
+                return Err(ConfigError::DerivedFrom {
+                    field: derived_from.clone(),
+                });
+            }
+        }
+
+        let delimiter = &self.csv_import.delimiter_text;
+        self.csv_import.delimiter = match delimiter.as_bytes() {
+            [byte] if byte.is_ascii() => *byte,
+            _ => {
+                return Err(ConfigError::Delimiter {
+                    delimiter: delimiter.clone(),
+                })
+            }
+        };
+
+        let mut patterns = HashMap::new();
+        for field in self.validation_rules.person.field_rules() {
+            if field.type_ == FieldType::Regex && field.pattern.is_none() {
+                return Err(ConfigError::MissingPattern { field: field.name });
+            }
+            if let Some(pattern) = &field.pattern {
+                let reg_exp = Patterns::compile(pattern).map_err(|error| ConfigError::Pattern {
+                    field: field.name.clone(),
+                    error,
+                })?;
+                patterns.insert(pattern.clone(), reg_exp);
+            }
+        }
+        self.patterns = Patterns(patterns);
+
+        self.postcodes = match &self.validation_rules.person.location.postcodes {
+            Some(path) => Postcodes::load(path).map_err(|error| ConfigError::Dataset {
+                path: path.clone(),
+                error,
+            })?,
+            None => Postcodes::default(),
+        };
+        self.occupations = match &self.validation_rules.person.occupation.codes {
+            Some(path) => Occupations::load(path).map_err(|error| ConfigError::Dataset {
+                path: path.clone(),
+                error,
+            })?,
+            None => Occupations::default(),
+        };
+        Ok(())
+    }
+}
+
+/// # Summary
+/// The compiled regular expressions of the config, keyed by their `pattern` attribute.
+#[derive(Debug, Clone, Default)]
+pub struct Patterns(HashMap<String, Regex>);
+
+impl Patterns {
+    /// Anchors the pattern so it has to match the whole value.
+    fn compile(pattern: &str) -> Result<Regex, regex::Error> {
+        Regex::new(&format!("^(?:{})$", pattern))
+    }
+
+    ///# Checks a value against a pattern
+    ///
+    /// Uses the expression compiled by [`Config::compile`], which compiles every pattern of the config.
+    ///
+    /// # Panics
+    ///
+    /// If the pattern was not compiled with the config.
+    pub fn is_match(&self, pattern: &str, value: &str) -> bool {
+        match self.0.get(pattern) {
+            Some(reg_exp) => reg_exp.is_match(value),
+            None => panic!("pattern `{}` was not compiled with the config", pattern),
+        }
+    }
+}
+
+impl PartialEq for Patterns {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.len() == other.0.len() && self.0.keys().all(|pattern| other.0.contains_key(pattern))
+    }
+}
+
+/// # Summary
+/// The reasons a config file is rejected.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The XML document doesn't match the config structure
+    Xml(quick_xml::DeError),
+    /// The `pattern` attribute of a field is no valid regular expression
+    Pattern { field: String, error: regex::Error },
+    /// A field of type `regex` has no `pattern` attribute
+    MissingPattern { field: String },
+    /// A dataset file referenced in the config can't be read
+    Dataset { path: String, error: std::io::Error },
+    /// The `maxValue` of a field is larger than the values the field can store
+    TooLarge { field: String, max: u32 },
+    /// The age is derived from a field which is no date field
+    DerivedFrom { field: String },
+    /// The CSV delimiter is not a single ASCII character
+    Delimiter { delimiter: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Xml(error) => write!(f, "invalid XML config: {}", error),
+            ConfigError::Pattern { field, error } => {
+                write!(f, "invalid pattern of field `{}`: {}", field, error)
+            }
+            ConfigError::MissingPattern { field } => {
+                write!(f, "field `{}` of type regex has no pattern", field)
+            }
+            ConfigError::Dataset { path, error } => {
+                write!(f, "could not read dataset `{}`: {}", path, error)
+            }
+            ConfigError::TooLarge { field, max } => {
+                write!(f, "maxValue of field `{}` is larger than {}", field, max)
+            }
+            ConfigError::DerivedFrom { field } => {
+                write!(f, "age is derived from `{}`, which is no date field", field)
+            }
+            ConfigError::Delimiter { delimiter } => {
+                write!(
+                    f,
+                    "CSV delimiter `{}` is not a single ASCII character",
+                    delimiter
+                )
+            }
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Default, Deserialize, Clone)]
@@ -20,6 +196,53 @@ pub struct DefaultSettings {
     pub language: String,
 }
 
+/// # Summary
+/// How the columns of an imported CSV file map to the fields of a user.
+///
+/// Columns named like a field (`first_name`, `ahv_nr`, ...) are mapped without a `<column>` entry,
+/// headers are compared without case and accents. Columns which map to no field are not imported.
+/// # Examples
+///
+/// ```xml
+/// <csvImport delimiter=";">
+///     <column header="Vorname" field="first_name" />
+///     <column header="AHV-Nr." field="ahv_nr" />
+/// </csvImport>
+/// ```
+#[derive(Debug, PartialEq, Deserialize, Clone)]
+#[serde(default)]
+#[serde(rename = "csvImport")]
+pub struct CsvImport {
+    /// The `delimiter` attribute as written in the config
+    #[serde(rename = "@delimiter")]
+    pub delimiter_text: String,
+    /// The delimiter as byte, checked by [`Config::compile`]
+    #[serde(skip)]
+    pub delimiter: u8,
+    #[serde(rename = "column")]
+    pub columns: Vec<CsvColumn>,
+}
+
+impl Default for CsvImport {
+    fn default() -> Self {
+        CsvImport {
+            delimiter_text: ";".to_string(),
+            delimiter: b';',
+            columns: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Default, Deserialize, Clone)]
+#[serde(default)]
+#[serde(rename = "column")]
+pub struct CsvColumn {
+    #[serde(rename = "@header")]
+    pub header: String,
+    #[serde(rename = "@field")]
+    pub field: String,
+}
+
 #[derive(Debug, PartialEq, Default, Deserialize, Clone)]
 #[serde(default)]
 #[serde(rename = "validationRules")]
@@ -36,8 +259,137 @@ pub struct Person {
     pub age: Age,
     #[serde(rename = "email")]
     pub email: Email,
+    #[serde(rename = "phone")]
+    pub phone: Phone,
     #[serde(rename = "pensum")]
     pub pensum: Pensum,
+    #[serde(rename = "location")]
+    pub location: Location,
+    #[serde(rename = "occupation")]
+    pub occupation: Occupation,
+    #[serde(rename = "ahv")]
+    pub ahv: Ahv,
+    #[serde(rename = "dateOfBirth")]
+    pub date_of_birth: DateOfBirth,
+    /// Additional fields declared with `<field name="..." type="..." />`
+    #[serde(rename = "field")]
+    pub fields: Vec<Field>,
+    /// Cross-field rules declared with `<rule>`
+    #[serde(rename = "rule")]
+    pub rules: Vec<Rule>,
+}
+
+impl Person {
+    ///# All fields of a person
+    ///
+    /// Turns the built-in elements (`name`, `age`, `pensum`, ...) into field declarations
+    /// and appends the additional `<field>` declarations, so the rule engine can treat them all the same.
+    pub fn field_rules(&self) -> Vec<Field> {
+        let mut rules = vec![
+            Field {
+                name: "first_name".to_string(),
+                type_: FieldType::String,
+                required: true,
+                min: Some(self.name.min),
+                max: Some(self.name.max),
+                pattern: self.name.pattern.clone(),
+                unit: self.name.unit,
+                normalize: self.name.normalize.clone(),
+                ..Field::default()
+            },
+            Field {
+                name: "last_name".to_string(),
+                type_: FieldType::String,
+                required: true,
+                min: Some(self.name.min),
+                max: Some(self.name.max),
+                pattern: self.name.pattern.clone(),
+                unit: self.name.unit,
+                normalize: self.name.normalize.clone(),
+                ..Field::default()
+            },
+            Field {
+                name: "age".to_string(),
+                type_: FieldType::Integer,
+                required: true,
+                min: Some(self.age.min),
+                max: Some(self.age.max),
+                ..Field::default()
+            },
+            Field {
+                name: "pensum".to_string(),
+                type_: FieldType::Integer,
+                required: true,
+                min: Some(self.pensum.min_value),
+                max: Some(self.pensum.max_value),
+                default: self.pensum.default.map(|default| default.to_string()),
+                ..Field::default()
+            },
+            Field {
+                name: "location".to_string(),
+                type_: match self.location.postcodes {
+                    Some(_) => FieldType::SwissLocation,
+                    None => FieldType::String,
+                },
+                required: true,
+                min: Some(self.location.min),
+                max: Some(self.location.max),
+                unit: self.location.unit,
+                pattern: self.location.pattern.clone(),
+                normalize: self.location.normalize.clone(),
+                allowed: self.location.allowed.clone(),
+                default: self.location.default.clone(),
+                ..Field::default()
+            },
+            Field {
+                name: "occupation".to_string(),
+                type_: match self.occupation.codes {
+                    Some(_) => FieldType::Occupation,
+                    None => FieldType::String,
+                },
+                required: true,
+                min: Some(self.occupation.min),
+                max: Some(self.occupation.max),
+                unit: self.occupation.unit,
+                pattern: self.occupation.pattern.clone(),
+                normalize: self.occupation.normalize.clone(),
+                allowed: self.occupation.allowed.clone(),
+                default: self.occupation.default.clone(),
+                ..Field::default()
+            },
+            Field {
+                name: "ahv_nr".to_string(),
+                type_: FieldType::Ahv,
+                required: true,
+                normalize: self.ahv.normalize.clone(),
+                ..Field::default()
+            },
+            Field {
+                name: "date_of_birth".to_string(),
+                type_: FieldType::Date,
+                required: self.date_of_birth.required,
+                min_date: self.date_of_birth.min_date,
+                max_date: self.date_of_birth.max_date,
+                past: self.date_of_birth.past,
+                ..Field::default()
+            },
+            Field {
+                name: "email".to_string(),
+                type_: FieldType::Email,
+                required: self.email.required,
+                normalize: self.email.normalize.clone(),
+                ..Field::default()
+            },
+            Field {
+                name: "phone".to_string(),
+                type_: FieldType::Phone,
+                required: self.phone.required,
+                ..Field::default()
+            },
+        ];
+        rules.extend(self.fields.iter().cloned());
+        rules
+    }
 }
 
 #[derive(Debug, PartialEq, Default, Deserialize, Clone)]
@@ -50,6 +402,13 @@ pub struct Name {
     pub min: u32,
     #[serde(rename = "@max")]
     pub max: u32,
+    /// Optional regular expression first and last name have to match
+    #[serde(rename = "@pattern")]
+    pub pattern: Option<String>,
+    #[serde(rename = "@unit")]
+    pub unit: LengthUnit,
+    #[serde(rename = "@normalize")]
+    pub normalize: Vec<NormalizeStep>,
 }
 
 #[derive(Debug, PartialEq, Default, Deserialize, Clone)]
@@ -62,14 +421,93 @@ pub struct Age {
     pub min: u32,
     #[serde(rename = "@maxValue")]
     pub max: u32,
+    /// Date field the age is derived from, e.g. `date_of_birth`
+    #[serde(rename = "@derivedFrom")]
+    pub derived_from: Option<String>,
 }
 
-#[derive(Debug, PartialEq, Default, Deserialize, Clone)]
+#[derive(Debug, PartialEq, Deserialize, Clone)]
 #[serde(default)]
 #[serde(rename = "email")]
 pub struct Email {
     #[serde(rename = "@type")]
     pub type_: String,
+    #[serde(rename = "@required")]
+    pub required: bool,
+    #[serde(rename = "@maxLength")]
+    pub max_length: u32,
+    #[serde(rename = "@allowIdn")]
+    pub allow_idn: bool,
+    #[serde(rename = "@allowPlus")]
+    pub allow_plus: bool,
+    #[serde(rename = "@normalize")]
+    pub normalize: Vec<NormalizeStep>,
+    /// If not empty, only addresses of these domains are accepted
+    #[serde(rename = "domain")]
+    pub allowed_domains: Vec<String>,
+}
+
+impl Default for Email {
+    fn default() -> Self {
+        Email {
+            type_: "email".to_string(),
+            required: false,
+            max_length: 254,
+            allow_idn: true,
+            allow_plus: true,
+            normalize: Vec::new(),
+            allowed_domains: Vec::new(),
+        }
+    }
+}
+
+/// # Summary
+/// The phone number of a user, stored in the E.164 notation `+41791234567`.
+///
+/// Swiss numbers are always accepted, numbers of other countries only if their country code is listed,
+/// e.g. `countryCodes="49 33 423"`.
+#[derive(Debug, PartialEq, Default, Deserialize, Clone)]
+#[serde(default)]
+#[serde(rename = "phone")]
+pub struct Phone {
+    #[serde(rename = "@type")]
+    pub type_: String,
+    #[serde(rename = "@required")]
+    pub required: bool,
+    /// Country codes of other countries whose numbers are accepted
+    #[serde(rename = "@countryCodes")]
+    pub country_codes: Vec<String>,
+}
+
+/// # Summary
+/// The date of birth, the age of a user is derived from it.
+#[derive(Debug, PartialEq, Default, Deserialize, Clone)]
+#[serde(default)]
+#[serde(rename = "dateOfBirth")]
+pub struct DateOfBirth {
+    #[serde(rename = "@type")]
+    pub type_: String,
+    #[serde(rename = "@required")]
+    pub required: bool,
+    /// Earliest accepted date
+    #[serde(rename = "@minDate")]
+    pub min_date: Option<NaiveDate>,
+    /// Latest accepted date
+    #[serde(rename = "@maxDate")]
+    pub max_date: Option<NaiveDate>,
+    /// Only accept dates up to today
+    #[serde(rename = "@past")]
+    pub past: bool,
+}
+
+#[derive(Debug, PartialEq, Default, Deserialize, Clone)]
+#[serde(default)]
+#[serde(rename = "ahv")]
+pub struct Ahv {
+    #[serde(rename = "@type")]
+    pub type_: String,
+    #[serde(rename = "@normalize")]
+    pub normalize: Vec<NormalizeStep>,
 }
 
 #[derive(Debug, PartialEq, Default, Deserialize, Clone)]
@@ -82,6 +520,309 @@ pub struct Pensum {
     pub min_value: u32,
     #[serde(rename = "@maxValue")]
     pub max_value: u32,
+    /// Value used on create if the pensum is omitted
     #[serde(rename = "@default")]
-    pub default: u32,
+    pub default: Option<u32>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Clone)]
+#[serde(default)]
+#[serde(rename = "location")]
+pub struct Location {
+    #[serde(rename = "@type")]
+    pub type_: String,
+    #[serde(rename = "@min")]
+    pub min: u32,
+    #[serde(rename = "@max")]
+    pub max: u32,
+    #[serde(rename = "@unit")]
+    pub unit: LengthUnit,
+    /// Optional regular expression the whole value has to match
+    #[serde(rename = "@pattern")]
+    pub pattern: Option<String>,
+    #[serde(rename = "@normalize")]
+    pub normalize: Vec<NormalizeStep>,
+    /// If not empty, only these values are accepted
+    #[serde(rename = "value")]
+    pub allowed: Vec<String>,
+    /// Value used on create if the field is omitted
+    #[serde(rename = "@default")]
+    pub default: Option<String>,
+    /// Path of the postcode dataset, if set the location is validated as postcode and locality
+    #[serde(rename = "@postcodes")]
+    pub postcodes: Option<String>,
+}
+
+impl Default for Location {
+    fn default() -> Self {
+        Location {
+            type_: "string".to_string(),
+            min: 2,
+            max: 55,
+            unit: LengthUnit::default(),
+            pattern: None,
+            normalize: Vec::new(),
+            allowed: Vec::new(),
+            default: None,
+            postcodes: None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Deserialize, Clone)]
+#[serde(default)]
+#[serde(rename = "occupation")]
+pub struct Occupation {
+    #[serde(rename = "@type")]
+    pub type_: String,
+    #[serde(rename = "@min")]
+    pub min: u32,
+    #[serde(rename = "@max")]
+    pub max: u32,
+    #[serde(rename = "@unit")]
+    pub unit: LengthUnit,
+    /// Optional regular expression the whole value has to match
+    #[serde(rename = "@pattern")]
+    pub pattern: Option<String>,
+    #[serde(rename = "@normalize")]
+    pub normalize: Vec<NormalizeStep>,
+    /// If not empty, only these values are accepted
+    #[serde(rename = "value")]
+    pub allowed: Vec<String>,
+    /// Value used on create if the field is omitted
+    #[serde(rename = "@default")]
+    pub default: Option<String>,
+    /// Path of the occupation code list, if set only listed occupations are accepted
+    #[serde(rename = "@codes")]
+    pub codes: Option<String>,
+}
+
+impl Default for Occupation {
+    fn default() -> Self {
+        Occupation {
+            type_: "string".to_string(),
+            min: 2,
+            max: 55,
+            unit: LengthUnit::default(),
+            pattern: None,
+            normalize: Vec::new(),
+            allowed: Vec::new(),
+            default: None,
+            codes: None,
+        }
+    }
+}
+
+/// How the length of a text is measured, selected with the `unit` attribute.
+#[derive(Debug, PartialEq, Default, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum LengthUnit {
+    /// UTF-8 bytes, "Zürich" has a length of 7
+    Bytes,
+    /// Unicode scalar values, "Zürich" has a length of 6
+    #[default]
+    Chars,
+    /// User-perceived characters (extended grapheme clusters), "👍🏽" has a length of 1
+    Graphemes,
+}
+
+/// A step of the `normalize` attribute, e.g. `normalize="trim collapse nfc"`.
+/// The steps are applied in the given order before a value is validated and stored.
+#[derive(Debug, PartialEq, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum NormalizeStep {
+    /// Removes leading and trailing whitespace
+    Trim,
+    /// Replaces every run of whitespace with a single space
+    Collapse,
+    /// Unicode normalization form C, e.g. "e" with a combining accent becomes "é"
+    Nfc,
+    /// Upper case first letter of every word, lower case the rest, e.g. "jean-luc" becomes "Jean-Luc"
+    Title,
+    /// Lower case the whole value
+    Lowercase,
+    /// Writes AHV numbers in the dotted form `756.XXXX.XXXX.XX`
+    Ahv,
+}
+
+/// The type of a field declared with `<field type="..." />`
+#[derive(Debug, PartialEq, Default, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldType {
+    /// Text with optional length limits, `pattern` and allowed values
+    #[default]
+    String,
+    /// Whole number within `min` and `max`, given as JSON number or numeric string
+    Integer,
+    /// Email address, checked with the settings of the `<email>` element
+    Email,
+    /// Swiss AHV number
+    Ahv,
+    /// Text which has to match `pattern`, the pattern is required
+    Regex,
+    /// Text which has to be one of the `<value>` children
+    Enum,
+    /// Swiss postcode and locality like `8001 Zürich`, checked against the dataset of `location@postcodes`
+    #[serde(rename = "swiss_location")]
+    SwissLocation,
+    /// Code or label from the code list of `occupation@codes`
+    Occupation,
+    /// Date like `1990-05-17` or `17.05.1990` within `minDate` and `maxDate`
+    Date,
+    /// Swiss company identification number like `CHE-123.456.788`
+    Uid,
+    /// Swiss or Liechtenstein IBAN
+    Iban,
+    /// Phone number, checked with the settings of the `<phone>` element and stored as E.164
+    Phone,
+}
+
+#[derive(Debug, PartialEq, Default, Deserialize, Clone)]
+#[serde(default)]
+#[serde(rename = "field")]
+pub struct Field {
+    #[serde(rename = "@name")]
+    pub name: String,
+    #[serde(rename = "@type")]
+    pub type_: FieldType,
+    #[serde(rename = "@required")]
+    pub required: bool,
+    /// Minimum length for text fields, minimum value for integers
+    #[serde(rename = "@min")]
+    pub min: Option<u32>,
+    /// Maximum length for text fields, maximum value for integers
+    #[serde(rename = "@max")]
+    pub max: Option<u32>,
+    /// How the length of text fields is measured
+    #[serde(rename = "@unit")]
+    pub unit: LengthUnit,
+    /// Optional regular expression the whole value has to match
+    #[serde(rename = "@pattern")]
+    pub pattern: Option<String>,
+    /// Steps applied to the value before it is validated and stored
+    #[serde(rename = "@normalize")]
+    pub normalize: Vec<NormalizeStep>,
+    /// If not empty, only these values are accepted
+    #[serde(rename = "value")]
+    pub allowed: Vec<String>,
+    /// Value used on create if the field is omitted
+    #[serde(rename = "@default")]
+    pub default: Option<String>,
+    /// Earliest accepted date of date fields
+    #[serde(rename = "@minDate")]
+    pub min_date: Option<NaiveDate>,
+    /// Latest accepted date of date fields
+    #[serde(rename = "@maxDate")]
+    pub max_date: Option<NaiveDate>,
+    /// Date fields only accept dates up to today
+    #[serde(rename = "@past")]
+    pub past: bool,
+}
+
+/// # Summary
+/// A cross-field rule: if every `<when>` condition holds, every `<then>` condition has to hold as well.
+/// # Examples
+///
+/// ```xml
+/// <rule name="apprentice_age">
+///     <when field="occupation" contains="Lernende" />
+///     <then field="age" maxValue="24" />
+/// </rule>
+/// ```
+#[derive(Debug, PartialEq, Default, Deserialize, Clone)]
+#[serde(default)]
+#[serde(rename = "rule")]
+pub struct Rule {
+    #[serde(rename = "@name")]
+    pub name: String,
+    #[serde(rename = "when")]
+    pub when: Vec<Condition>,
+    #[serde(rename = "then")]
+    pub then: Vec<Condition>,
+}
+
+/// # Summary
+/// A condition on a single field, every given attribute has to be satisfied.
+#[derive(Debug, PartialEq, Default, Deserialize, Clone)]
+#[serde(default)]
+pub struct Condition {
+    #[serde(rename = "@field")]
+    pub field: String,
+    /// The value equals this text (case insensitive)
+    #[serde(rename = "@equals")]
+    pub equals: Option<String>,
+    /// The value contains this text (case insensitive)
+    #[serde(rename = "@contains")]
+    pub contains: Option<String>,
+    /// The value is a number not below this value
+    #[serde(rename = "@minValue")]
+    pub min_value: Option<i64>,
+    /// The value is a number not above this value
+    #[serde(rename = "@maxValue")]
+    pub max_value: Option<i64>,
+}
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(person: &str) -> Result<Config, ConfigError> {
+        Config::from_xml(&format!(
+            "<config><validationRules><person>{}</person></validationRules></config>",
+            person
+        ))
+    }
+
+    #[test]
+    fn patterns_are_compiled_on_load() {
+        let config =
+            parse(r#"<field name="code" type="regex" pattern="[A-Z]{2}\d{4}" />"#).unwrap();
+        assert!(config.patterns.is_match(r"[A-Z]{2}\d{4}", "AB1234"));
+        assert!(!config.patterns.is_match(r"[A-Z]{2}\d{4}", "AB12345"));
+
+        let invalid = parse(r#"<field name="code" type="regex" pattern="[A-Z" />"#);
+        assert!(matches!(invalid, Err(ConfigError::Pattern { field, .. }) if field == "code"));
+
+        let missing = parse(r#"<field name="code" type="regex" />"#);
+        assert!(matches!(missing, Err(ConfigError::MissingPattern { field }) if field == "code"));
+    }
+
+    #[test]
+    fn ranges_have_to_fit_the_stored_type() {
+        assert!(parse(r#"<age type="integer" minValue="12" maxValue="255" />"#).is_ok());
+        let too_large = parse(r#"<pensum type="integer" minValue="10" maxValue="300" />"#);
+        assert!(matches!(
+            too_large,
+            Err(ConfigError::TooLarge { field, max: 255 }) if field == "pensum"
+        ));
+    }
+
+    #[test]
+    fn the_age_is_derived_from_a_date_field() {
+        assert!(
+            parse(r#"<age type="integer" maxValue="120" derivedFrom="date_of_birth" />"#).is_ok()
+        );
+        let not_a_date = parse(r#"<age type="integer" maxValue="120" derivedFrom="pensum" />"#);
+        assert!(matches!(not_a_date, Err(ConfigError::DerivedFrom { field }) if field == "pensum"));
+    }
+
+    #[test]
+    fn the_csv_delimiter_is_one_ascii_character() {
+        let config = |delimiter: &str| {
+            Config::from_xml(&format!(
+                r#"<config><csvImport delimiter="{}" /></config>"#,
+                delimiter
+            ))
+        };
+        assert_eq!(config(",").unwrap().csv_import.delimiter, b',');
+        assert_eq!(Config::default().csv_import.delimiter, b';');
+        for delimiter in ["§", ";;", ""] {
+            assert!(matches!(
+                config(delimiter),
+                Err(ConfigError::Delimiter { .. })
+            ));
+        }
+    }
 }
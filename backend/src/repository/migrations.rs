@@ -0,0 +1,234 @@
+//! This module contains the data migrations of the stored users.
+//!
+//! Every migration has a version, the applied versions are recorded in the `_migrations` collection so a migration
+//! only runs once. The migrations run on startup, or with `cargo run -- migrate [--dry-run]`.
+use crate::{
+    library::{
+        lib::{validate_ahv, validate_user},
+        messages::{message, Language},
+        normalizer::derived_age,
+    },
+    models::xml_models::Config,
+};
+use futures::{future::join_all, stream::TryStreamExt};
+use mongodb::{
+    bson::{doc, Bson, DateTime, Document},
+    options::FindOptions,
+    Collection, Database,
+};
+use serde_json::Value;
+use std::{
+    fmt,
+    io::{Error, ErrorKind},
+};
+
+/// Number of documents which are read and written at once
+const BATCH_SIZE: usize = 500;
+
+/// # Summary
+/// A conversion of the stored users.
+///
+/// `migrate` returns the fields to `$set` on a document, an empty document if nothing changes.
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    migrate: fn(&Document) -> Document,
+}
+
+/// All migrations in the order they are applied, new migrations are appended with the next version.
+pub const MIGRATIONS: &[Migration] = &[Migration {
+    version: 1,
+    name: "typed_user_fields",
+    migrate: typed_user_fields,
+}];
+
+/// Converts `age` and `pensum` stored as strings into numbers and writes `ahv_nr` in the canonical notation.
+/// Values which can't be converted are left unchanged, they show up as invalid in the report.
+fn typed_user_fields(document: &Document) -> Document {
+    let mut changes = Document::new();
+    for key in ["age", "pensum"] {
+        if let Ok(Ok(number)) = document.get_str(key).map(|text| text.trim().parse::<i32>()) {
+            changes.insert(key, number);
+        }
+    }
+    if let Ok(ahv_nr) = document.get_str("ahv_nr") {
+        if let Ok(canonical) = validate_ahv(ahv_nr) {
+            if canonical.to_string() != ahv_nr {
+                changes.insert("ahv_nr", canonical.to_string());
+            }
+        }
+    }
+    changes
+}
+
+/// # Summary
+/// The outcome of a migration run.
+#[derive(Debug, Default)]
+pub struct MigrationReport {
+    pub dry_run: bool,
+    /// Names of the migrations which were applied, or would be applied in a dry run
+    pub pending: Vec<&'static str>,
+    /// Number of converted documents
+    pub converted: u64,
+    /// Ids of the documents which fail the current config after the conversion, with the messages of the failed checks
+    pub invalid: Vec<(String, Vec<String>)>,
+}
+
+impl fmt::Display for MigrationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verb = if self.dry_run { "would be" } else { "were" };
+        if self.pending.is_empty() {
+            writeln!(f, "No pending migrations.")?;
+        } else {
+            writeln!(
+                f,
+                "Migrations {}: {}",
+                self.pending.join(", "),
+                if self.dry_run { "pending" } else { "applied" }
+            )?;
+        }
+        writeln!(f, "{} documents {} converted.", self.converted, verb)?;
+        writeln!(
+            f,
+            "{} documents fail the current config.",
+            self.invalid.len()
+        )?;
+        for (id, messages) in &self.invalid {
+            writeln!(f, "  {}: {}", id, messages.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+///# Runs the pending migrations
+///
+/// Reads the users in batches of [`BATCH_SIZE`], applies every pending migration and writes the changed fields.
+/// Every converted document is also validated against `config`, the failing ones are listed in the report.
+/// With `dry_run` nothing is written, and all documents are checked even if no migration is pending.
+pub async fn run_migrations(
+    db: &Database,
+    config: &Config,
+    dry_run: bool,
+) -> Result<MigrationReport, Error> {
+    let migrations = db.collection::<Document>("_migrations");
+    let applied = match migrations.distinct("version", None, None).await {
+        Ok(applied) => applied,
+        Err(_) => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Error reading migrations",
+            ))
+        }
+    };
+    let pending = MIGRATIONS
+        .iter()
+        .filter(|migration| !applied.contains(&Bson::Int32(migration.version)))
+        .collect::<Vec<_>>();
+
+    let mut report = MigrationReport {
+        dry_run,
+        pending: pending.iter().map(|migration| migration.name).collect(),
+        ..MigrationReport::default()
+    };
+    if pending.is_empty() && !dry_run {
+        return Ok(report);
+    }
+
+    let users = db.collection::<Document>("User");
+    let options = FindOptions::builder().batch_size(BATCH_SIZE as u32).build();
+    let mut cursor = match users.find(None, options).await {
+        Ok(cursor) => cursor,
+        Err(_) => return Err(Error::new(ErrorKind::InvalidInput, "Error reading users")),
+    };
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    loop {
+        let document = match cursor.try_next().await {
+            Ok(document) => document,
+            Err(_) => return Err(Error::new(ErrorKind::InvalidInput, "Error reading users")),
+        };
+        let done = document.is_none();
+        batch.extend(document);
+        if done || batch.len() == BATCH_SIZE {
+            migrate_batch(&users, config, &pending, &mut report, &batch).await?;
+            batch.clear();
+        }
+        if done {
+            break;
+        }
+    }
+
+    if !dry_run {
+        for migration in pending {
+            let record = doc! {
+                "version": migration.version,
+                "name": migration.name,
+                "applied_at": DateTime::now(),
+            };
+            if migrations.insert_one(record, None).await.is_err() {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Error recording migration",
+                ));
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Converts and validates one batch of documents, the changes are written concurrently.
+async fn migrate_batch(
+    users: &Collection<Document>,
+    config: &Config,
+    pending: &[&Migration],
+    report: &mut MigrationReport,
+    batch: &[Document],
+) -> Result<(), Error> {
+    let language = Language::from_code(&config.default_settings.language).unwrap_or_default();
+    let mut updates = Vec::new();
+
+    for document in batch {
+        // later migrations see the changes of the earlier ones
+        let mut migrated = document.clone();
+        let mut changes = Document::new();
+        for migration in pending {
+            let step = (migration.migrate)(&migrated);
+            migrated.extend(step.clone());
+            changes.extend(step);
+        }
+
+        let id = document.get("_id").cloned().unwrap_or(Bson::Null);
+        if let Ok(Value::Object(mut user)) = serde_json::to_value(&migrated) {
+            // the stored age gets stale, it is checked as it would be derived today
+            if let Some(age) = derived_age(config, &user) {
+                user.insert("age".to_string(), Value::from(age));
+            }
+            if let Err(failed) = validate_user(config, &user) {
+                let messages = failed
+                    .errors
+                    .iter()
+                    .map(|error| message(error, language))
+                    .collect();
+                let id = match &id {
+                    Bson::ObjectId(id) => id.to_hex(),
+                    id => id.to_string(),
+                };
+                report.invalid.push((id, messages));
+            }
+        }
+
+        if !changes.is_empty() {
+            report.converted += 1;
+            if !report.dry_run {
+                updates.push(users.update_one(doc! {"_id": id}, doc! {"$set": changes}, None));
+            }
+        }
+    }
+
+    if join_all(updates).await.iter().any(|result| result.is_err()) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Error converting users",
+        ));
+    }
+    Ok(())
+}
@@ -1 +1,2 @@
+pub mod migrations;
 pub mod mongodb_repo;
\ No newline at end of file
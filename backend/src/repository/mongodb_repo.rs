@@ -8,14 +8,18 @@ use dotenv::dotenv;
 
 use futures::stream::TryStreamExt;
 use mongodb::{
-    bson::{doc, oid::ObjectId},
+    bson::{doc, from_document, oid::ObjectId, to_document, Document},
+    error::{ErrorKind as MongoErrorKind, UNKNOWN_TRANSACTION_COMMIT_RESULT},
+    options::InsertManyOptions,
     results::{DeleteResult, InsertOneResult, UpdateResult},
-    Client, Collection,
+    Client, Collection, Cursor, Database,
 };
 
 use crate::models::db_models::{UpdatedUser, User};
 
 pub struct MongoRepo {
+    client: Client,
+    db: Database,
     col: Collection<User>,
 }
 
@@ -24,23 +28,29 @@ impl MongoRepo {
         dotenv().ok();
         let uri = match env::var("MONGOURI") {
             Ok(v) => v.to_string(),
-            Err(_) => format!("Error loading env variable"),
+            Err(_) => "Error loading env variable".to_string(),
         };
-        let client = match Client::with_uri_str(uri).await {
-            Ok(client) => client,
+        match MongoRepo::connect(&uri).await {
+            Ok(repo) => repo,
             Err(_) => panic!("Error connecting to database"),
-        };
+        }
+    }
 
+    /// Creates the repository for the server at `uri`, the connection is only opened by the first operation.
+    pub async fn connect(uri: &str) -> Result<Self, mongodb::error::Error> {
+        let client = Client::with_uri_str(uri).await?;
         let db = client.database("rust-api");
         let col: Collection<User> = db.collection("User");
-        MongoRepo { col }
+        Ok(MongoRepo { client, db, col })
+    }
+
+    /// The database of the users, used by the [migrations](crate::repository::migrations).
+    pub fn database(&self) -> &Database {
+        &self.db
     }
 
     pub async fn create_user(&self, new_user: User) -> Result<InsertOneResult, Error> {
-        let new_doc = User {
-            id: None,
-            ..new_user
-        };
+        let new_doc = new_user.with_id(None);
 
         let user = match self.col.insert_one(new_doc, None).await.ok() {
             Some(user) => user,
@@ -50,6 +60,91 @@ impl MongoRepo {
         Ok(user)
     }
 
+    ///# Inserts many users at once
+    ///
+    /// The ids are assigned before writing, the result has the id or the reason of the failure for every user in order.
+    /// With `atomic` the users are written in one transaction, so either all or none are stored and a failure is
+    /// returned as error. Transactions need a replica set, on a standalone server an atomic import always fails.
+    /// Otherwise every user is written independently of the others.
+    pub async fn import_users(
+        &self,
+        new_users: Vec<User>,
+        atomic: bool,
+    ) -> Result<Vec<Result<ObjectId, String>>, Error> {
+        if new_users.is_empty() {
+            return Ok(Vec::new());
+        }
+        let ids = new_users
+            .iter()
+            .map(|_| ObjectId::new())
+            .collect::<Vec<_>>();
+        let new_docs = new_users
+            .into_iter()
+            .zip(&ids)
+            .map(|(user, id)| user.with_id(Some(*id)))
+            .collect::<Vec<_>>();
+
+        if atomic {
+            return match self.insert_in_transaction(new_docs).await {
+                Ok(()) => Ok(ids.into_iter().map(Ok).collect()),
+                Err(error) => {
+                    dbg!(error);
+                    Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        "Error importing users, nothing was stored",
+                    ))
+                }
+            };
+        }
+
+        let options = InsertManyOptions::builder().ordered(false).build();
+        let write_errors = match self.col.insert_many(new_docs, options).await {
+            Ok(_) => Vec::new(),
+            Err(error) => match *error.kind {
+                MongoErrorKind::BulkWrite(failure) if failure.write_concern_error.is_none() => {
+                    failure.write_errors.unwrap_or_default()
+                }
+                _ => return Err(Error::new(ErrorKind::InvalidInput, "Error importing users")),
+            },
+        };
+
+        Ok(ids
+            .into_iter()
+            .enumerate()
+            .map(
+                |(index, id)| match write_errors.iter().find(|error| error.index == index) {
+                    Some(error) => Err(error.message.clone()),
+                    None => Ok(id),
+                },
+            )
+            .collect())
+    }
+
+    /// Inserts the users in a transaction, it is aborted if one of them can't be written.
+    async fn insert_in_transaction(
+        &self,
+        new_docs: Vec<User>,
+    ) -> Result<(), mongodb::error::Error> {
+        let mut session = self.client.start_session(None).await?;
+        session.start_transaction(None).await?;
+        if let Err(error) = self
+            .col
+            .insert_many_with_session(new_docs, None, &mut session)
+            .await
+        {
+            // the error of the insert is the one to report, a failed abort times out on the server
+            let _ = session.abort_transaction().await;
+            return Err(error);
+        }
+        // the outcome of a commit can be unknown after a network error, committing again is safe
+        match session.commit_transaction().await {
+            Err(error) if error.contains_label(UNKNOWN_TRANSACTION_COMMIT_RESULT) => {
+                session.commit_transaction().await
+            }
+            result => result,
+        }
+    }
+
     pub async fn get_user(&self, id: &String) -> Result<User, Error> {
         let obj_id = match ObjectId::parse_str(id) {
             Ok(obj_id) => obj_id,
@@ -65,39 +160,54 @@ impl MongoRepo {
         Ok(user_detail)
     }
 
+    ///# Reads a user together with its stored document
+    ///
+    /// The document is the precondition for [`MongoRepo::update_user`], the update is only written if it is still unchanged.
+    pub async fn get_user_snapshot(&self, id: &String) -> Result<(User, Document), Error> {
+        let obj_id = match ObjectId::parse_str(id) {
+            Ok(obj_id) => obj_id,
+            Err(_) => return Err(Error::new(ErrorKind::InvalidInput, "Invalid ID")),
+        };
+        let filter = doc! {"_id": obj_id};
+        let raw = self.col.clone_with_type::<Document>();
+        let snapshot = match raw.find_one(filter, None).await {
+            Ok(Some(snapshot)) => snapshot,
+            Ok(None) => return Err(Error::new(ErrorKind::NotFound, "User not found")),
+            Err(_) => return Err(Error::new(ErrorKind::InvalidInput, "Error getting user")),
+        };
+        let user = match from_document(snapshot.clone()) {
+            Ok(user) => user,
+            Err(_) => return Err(Error::new(ErrorKind::InvalidData, "Error reading user")),
+        };
+
+        Ok((user, snapshot))
+    }
+
+    ///# Updates a user if it wasn't changed in the meantime
+    ///
+    /// The update only matches if the stored document still equals `snapshot`, so reading, validating and writing is atomic.
+    /// A `matched_count` of `0` means the user was changed or deleted concurrently.
     pub async fn update_user(
         &self,
         id: &String,
+        snapshot: &Document,
         new_user: UpdatedUser,
     ) -> Result<UpdateResult, Error> {
         let obj_id = match ObjectId::parse_str(id) {
             Ok(obj_id) => obj_id,
             Err(_) => return Err(Error::new(ErrorKind::InvalidInput, "Invalid ID")),
         };
-        let filter = doc! {"_id": obj_id};
+        // $literal keeps string values starting with `$` from being read as field paths
+        let filter = doc! {
+            "_id": obj_id,
+            "$expr": { "$eq": ["$$ROOT", { "$literal": snapshot }] },
+        };
 
-        let mut update_doc = doc! {};
-        if let Some(first_name) = new_user.first_name {
-            update_doc.insert("first_name", first_name);
-        }
-        if let Some(last_name) = new_user.last_name {
-            update_doc.insert("last_name", last_name);
-        }
-        if let Some(age) = new_user.age {
-            update_doc.insert("age", age);
-        }
-        if let Some(pensum) = new_user.pensum {
-            update_doc.insert("pensum", pensum);
-        }
-        if let Some(location) = new_user.location {
-            update_doc.insert("location", location);
-        }
-        if let Some(occupation) = new_user.occupation {
-            update_doc.insert("occupation", occupation);
-        }
-        if let Some(ahv_nr) = new_user.ahv_nr {
-            update_doc.insert("ahv_nr", ahv_nr);
-        }
+        // only the fields present in the update are serialized
+        let update_doc = match to_document(&new_user) {
+            Ok(update_doc) => update_doc,
+            Err(_) => return Err(Error::new(ErrorKind::InvalidInput, "Error updating user")),
+        };
 
         let update = doc! {"$set": update_doc};
         let updated_doc = match self.col.update_one(filter, update, None).await.ok() {
@@ -121,6 +231,16 @@ impl MongoRepo {
         Ok(user_detail)
     }
 
+    ///# Reads all users one by one
+    ///
+    /// Unlike [`MongoRepo::get_all_users`] the users are not collected, so the whole collection can be streamed.
+    pub async fn user_cursor(&self) -> Result<Cursor<User>, Error> {
+        match self.col.find(None, None).await {
+            Ok(cursor) => Ok(cursor),
+            Err(_) => Err(Error::new(ErrorKind::InvalidInput, "Error getting users")),
+        }
+    }
+
     pub async fn get_all_users(&self) -> Result<Vec<User>, Error> {
         let mut cursors = match self.col.find(None, None).await.ok() {
             Some(user) => user,
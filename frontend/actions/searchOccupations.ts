@@ -0,0 +1,14 @@
+import { OccupationCode } from "@/types";
+
+const API_URL = process.env.NEXT_PUBLIC_MONGO_API;
+
+export const searchOccupations = async (query: string): Promise<OccupationCode[]> => {
+    try {
+        const res = await fetch(`${API_URL}/occupations?q=${encodeURIComponent(query)}`);
+        const data = await res.json();
+        return data;
+    } catch (error) {
+        console.log(error);
+        return [];
+    }
+}
@@ -1,22 +1,24 @@
 "use client";
 
-import React from "react";
+import React, { useEffect } from "react";
 import Modal from "./Modal";
 import useAddUserModal from "@/hooks/useAddUserModal";
 import { FieldValues, SubmitHandler, useForm } from "react-hook-form";
 import { toast } from "react-hot-toast";
 import { useState } from "react";
-import { User } from "@/types";
+import { OccupationCode, User } from "@/types";
 import { addUser } from "@/actions/addUser";
 import Input from "./Input";
 import { useUsers } from "@/hooks/useUsers";
+import { searchOccupations } from "@/actions/searchOccupations";
+import useDebounce from "@/hooks/useDebounce";
 
 const AddUserModal = () => {
   const { addStateUser } = useUsers();
   const [isLoading, setIsLoading] = useState(false);
   const { onClose, isOpen } = useAddUserModal();
 
-  const { register, handleSubmit, reset } = useForm<FieldValues>({
+  const { register, handleSubmit, reset, watch } = useForm<FieldValues>({
     defaultValues: {
       first_name: "",
       last_name: "",
@@ -28,6 +30,18 @@ const AddUserModal = () => {
     },
   });
 
+  // Suggestions from the occupation code list, empty if the backend has none
+  const [occupations, setOccupations] = useState<OccupationCode[]>([]);
+  const occupationQuery = useDebounce<string>(watch("occupation"), 300);
+
+  useEffect(() => {
+    if (!occupationQuery) {
+      setOccupations([]);
+      return;
+    }
+    searchOccupations(occupationQuery).then(setOccupations);
+  }, [occupationQuery]);
+
   const onChange = (open: boolean) => {
     if (!open) {
       reset();
@@ -122,7 +136,19 @@ const AddUserModal = () => {
             disabled={isLoading}
             {...register("occupation", { required: true })}
             placeholder="Occupation"
+            list="occupation-suggestions"
+            autoComplete="off"
           />
+          <datalist id="occupation-suggestions">
+            {occupations.map((occupation) => (
+              <option
+                key={`${occupation.code}-${occupation.label}`}
+                value={occupation.label}
+              >
+                {occupation.code}
+              </option>
+            ))}
+          </datalist>
           AHV Nummer:
           <Input
             id="ahv_nr"
@@ -5,6 +5,8 @@ import { FieldValues, SubmitHandler, useForm } from "react-hook-form";
 import { toast } from "react-hot-toast";
 import { useState } from "react";
 import { User } from "@/types";
+import formatLocation from "@/utils/formatLocation";
+import formatOccupation from "@/utils/formatOccupation";
 import Input from "./Input";
 import { useUsers } from "@/hooks/useUsers";
 import { updateUser } from "@/actions/updateUser";
@@ -22,8 +24,8 @@ const EditUserModal = () => {
       setValue("last_name", user.last_name);
       setValue("age", user.age);
       setValue("pensum", user.pensum);
-      setValue("location", user.location);
-      setValue("occupation", user.occupation);
+      setValue("location", formatLocation(user.location));
+      setValue("occupation", formatOccupation(user.occupation));
       setValue("ahv_nr", user.ahv_nr);
     }
   }, [user, setValue]);
@@ -39,8 +41,8 @@ const EditUserModal = () => {
     setValue("last_name", user.last_name);
     setValue("age", user.age);
     setValue("pensum", user.pensum);
-    setValue("location", user.location);
-    setValue("occupation", user.occupation);
+    setValue("location", formatLocation(user.location));
+    setValue("occupation", formatOccupation(user.occupation));
     setValue("ahv_nr", user.ahv_nr);
 
     onOpen(user);
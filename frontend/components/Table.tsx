@@ -1,4 +1,6 @@
 import { User } from "@/types";
+import formatLocation from "@/utils/formatLocation";
+import formatOccupation from "@/utils/formatOccupation";
 import React, { useState, useEffect } from "react";
 import TableRow from "./TableRow";
 import { deleteUser } from "@/actions/deleteUser";
@@ -37,8 +39,8 @@ export const Table: React.FC<TableProps> = ({ filterValue, users }) => {
       return (
         user.first_name.toLowerCase().includes(searchValue) ||
         user.last_name.toLowerCase().includes(searchValue) ||
-        user.location.toLowerCase().includes(searchValue) ||
-        user.occupation.toLowerCase().includes(searchValue) ||
+        formatLocation(user.location).toLowerCase().includes(searchValue) ||
+        formatOccupation(user.occupation).toLowerCase().includes(searchValue) ||
         user.ahv_nr.toLowerCase().includes(searchValue)
       );
     });
@@ -49,8 +51,8 @@ export const Table: React.FC<TableProps> = ({ filterValue, users }) => {
     return (
       user.first_name.toLowerCase().includes(searchValue) ||
       user.last_name.toLowerCase().includes(searchValue) ||
-      user.location.toLowerCase().includes(searchValue) ||
-      user.occupation.toLowerCase().includes(searchValue) ||
+      formatLocation(user.location).toLowerCase().includes(searchValue) ||
+      formatOccupation(user.occupation).toLowerCase().includes(searchValue) ||
       user.ahv_nr.toLowerCase().includes(searchValue)
     );
   });
@@ -2,6 +2,8 @@ import { deleteUser } from "@/actions/deleteUser";
 import useEditUserModal from "@/hooks/useEditUserModal";
 import { useUsers } from "@/hooks/useUsers";
 import { User } from "@/types";
+import formatLocation from "@/utils/formatLocation";
+import formatOccupation from "@/utils/formatOccupation";
 import { useEffect, useState } from "react";
 
 interface TableRowProps {
@@ -37,8 +39,8 @@ const TableRow: React.FC<TableRowProps> = ({ user, handleDelete }) => {
           {user.first_name}
         </th>
         <td className="px-6 py-4">{user.last_name}</td>
-        <td className="px-6 py-4">{user.location}</td>
-        <td className="px-6 py-4">{user.occupation}</td>
+        <td className="px-6 py-4">{formatLocation(user.location)}</td>
+        <td className="px-6 py-4">{formatOccupation(user.occupation)}</td>
         <td className="px-6 py-4">{user.ahv_nr}</td>
 
         <td className="flex items-center px-6 py-4 space-x-3">
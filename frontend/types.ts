@@ -1,3 +1,18 @@
+export interface SwissLocation {
+    plz: string;
+    locality: string;
+    canton: string;
+}
+
+export type Location = string | SwissLocation;
+
+export interface OccupationCode {
+    code: string;
+    label: string;
+}
+
+export type Occupation = string | OccupationCode;
+
 export interface User {
     _id?: {
         $oid: string;
@@ -6,10 +21,12 @@ export interface User {
     last_name: string;
     age: number;
     pensum: number;
-    location: string;
-    occupation: string;
+    location: Location;
+    occupation: Occupation;
     ahv_nr: string;
-
+    email?: string;
+    phone?: string;
+    date_of_birth?: string;
 }
 
 export interface newUser {
@@ -20,7 +37,10 @@ export interface newUser {
     last_name?: string;
     age?: number;
     pensum?: number;
-    location?: string;
-    occupation?: string;
+    location?: Location;
+    occupation?: Occupation;
     ahv_nr?: string;
+    email?: string;
+    phone?: string;
+    date_of_birth?: string;
 }
\ No newline at end of file
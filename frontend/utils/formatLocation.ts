@@ -0,0 +1,11 @@
+import { Location } from "@/types";
+
+// Swiss locations are returned as object when the postcode dataset is enabled
+const formatLocation = (location: Location): string => {
+  if (typeof location === "string") {
+    return location;
+  }
+  return `${location.plz} ${location.locality}`;
+};
+
+export default formatLocation;
@@ -0,0 +1,11 @@
+import { Occupation } from "@/types";
+
+// Occupations are returned as object when the occupation code list is enabled
+const formatOccupation = (occupation: Occupation): string => {
+  if (typeof occupation === "string") {
+    return occupation;
+  }
+  return occupation.label;
+};
+
+export default formatOccupation;